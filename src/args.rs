@@ -1,4 +1,10 @@
-use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use catbox::{AlbumShort, LitterExpiry, history::Service};
+use chrono::NaiveDate;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use crate::{config::Key, download::Conflict, output::OutputFormat, walk::Symlinks};
 
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum CatboxCommand {
@@ -6,15 +12,46 @@ pub enum CatboxCommand {
     Delete(Delete),
     Album(Album),
     Litter(Litter),
+    Download(Download),
+    History(History),
+    Login(Login),
+    Logout(Logout),
+    Config(Config),
 }
 
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum AlbumCommand {
+    Show(AlbumShow),
     Create(AlbumCreate),
     Edit(AlbumEdit),
+    Update(AlbumUpdate),
     Add(AlbumAdd),
     Remove(AlbumRemove),
     Delete(AlbumDelete),
+    Sync(AlbumSync),
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum HistoryCommand {
+    List(HistoryList),
+    Search(HistorySearch),
+    Show(HistoryShow),
+    Export(HistoryExport),
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ConfigCommand {
+    Get(ConfigGet),
+    Set(ConfigSet),
+    Unset(ConfigUnset),
+    List(ConfigList),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
 }
 
 #[derive(Parser, Debug)]
@@ -27,13 +64,187 @@ pub enum AlbumCommand {
 pub struct CatboxArgs {
     #[command(subcommand)]
     pub command: CatboxCommand,
+    #[arg(
+        global = true,
+        long,
+        help = "Config file. Read from CATBOX_CONFIG env variable if not provided. (Default: \
+                $XDG_CONFIG_HOME/catbox/config.toml)"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(
+        global = true,
+        long,
+        help = "Profile of the config file to use. Read from CATBOX_PROFILE env variable if not \
+                provided."
+    )]
+    pub profile: Option<String>,
     #[arg(
         global = true,
         short,
         long = "user",
-        help = "Catbox API user hash. Read from CATBOX_USER_HASH env variable if not provided."
+        help = "Catbox API user hash. Read from CATBOX_USER_HASH env variable or the profile if \
+                not provided."
     )]
     pub user_hash: Option<String>,
+    #[arg(
+        global = true,
+        long,
+        help = "Catbox API URL. Read from CATBOX_API_URL env variable or the profile if not \
+                provided."
+    )]
+    pub api_url: Option<String>,
+    #[arg(
+        global = true,
+        long,
+        help = "Litterbox API URL. Read from LITTER_API_URL env variable or the profile if not \
+                provided."
+    )]
+    pub litter_api_url: Option<String>,
+    #[arg(
+        global = true,
+        long,
+        help = "Base URL of uploaded files. Read from CATBOX_FILES_URL env variable or the \
+                profile if not provided."
+    )]
+    pub files_url: Option<String>,
+    #[arg(
+        global = true,
+        long,
+        help = "Base URL of albums. Read from CATBOX_ALBUM_URL env variable or the profile if \
+                not provided."
+    )]
+    pub album_url: Option<String>,
+    #[arg(
+        global = true,
+        long,
+        help = "Times to retry requests that failed because of connection problems or server \
+                errors. Read from CATBOX_RETRIES env variable or the profile if not provided."
+    )]
+    pub retries: Option<u32>,
+    #[arg(
+        global = true,
+        long,
+        help = "Seconds before a request times out. Read from CATBOX_TIMEOUT env variable or the \
+                profile if not provided."
+    )]
+    pub timeout: Option<u64>,
+    #[arg(
+        global = true,
+        long,
+        help = "Number of files to upload or download at the same time. Read from \
+                CATBOX_CONCURRENCY env variable or the profile if not provided. (Default: 10)"
+    )]
+    pub concurrency: Option<usize>,
+    #[arg(
+        global = true,
+        long,
+        value_enum,
+        help = "Format of the results. Read from CATBOX_OUTPUT env variable or the profile if \
+                not provided. (Default: text)"
+    )]
+    pub output: Option<OutputFormat>,
+    #[arg(
+        global = true,
+        long,
+        help = "Don't check file sizes and types before uploading, e.g. for self-hosted servers \
+                with other limits"
+    )]
+    pub no_limits: bool,
+    #[arg(
+        global = true,
+        long,
+        help = "Detect the type of files from their contents, send it with the upload and warn \
+                if it doesn't match the extension"
+    )]
+    pub sniff: bool,
+    #[arg(
+        global = true,
+        long,
+        help = "Like --sniff, but also add the right extension to the uploaded name if it's \
+                missing or wrong"
+    )]
+    pub fix_extension: bool,
+    #[arg(
+        global = true,
+        long,
+        help = "Don't record uploads, albums and deletions in the local history"
+    )]
+    pub no_history: bool,
+}
+
+#[derive(Debug, PartialEq, Args)]
+pub struct ClientArgs {
+    #[arg(from_global)]
+    pub config: Option<PathBuf>,
+    #[arg(from_global)]
+    pub profile: Option<String>,
+    #[arg(from_global)]
+    pub api_url: Option<String>,
+    #[arg(from_global)]
+    pub litter_api_url: Option<String>,
+    #[arg(from_global)]
+    pub files_url: Option<String>,
+    #[arg(from_global)]
+    pub album_url: Option<String>,
+    #[arg(from_global)]
+    pub retries: Option<u32>,
+    #[arg(from_global)]
+    pub timeout: Option<u64>,
+    #[arg(from_global)]
+    pub concurrency: Option<usize>,
+    #[arg(from_global)]
+    pub output: Option<OutputFormat>,
+    #[arg(from_global)]
+    pub no_limits: bool,
+    #[arg(from_global)]
+    pub sniff: bool,
+    #[arg(from_global)]
+    pub fix_extension: bool,
+    #[arg(from_global)]
+    pub no_history: bool,
+}
+
+#[derive(Debug, PartialEq, Args)]
+pub struct WalkArgs {
+    #[arg(
+        short,
+        long,
+        help = "Upload the files in directories and their subdirectories"
+    )]
+    pub recursive: bool,
+    #[arg(
+        long,
+        requires = "recursive",
+        help = "Only upload files in directories matching this glob, e.g. '*.png' or \
+                'photos/**/*.jpg'. Can be repeated."
+    )]
+    pub include: Vec<String>,
+    #[arg(
+        long,
+        requires = "recursive",
+        help = "Leave out files and directories matching this glob. Can be repeated."
+    )]
+    pub exclude: Vec<String>,
+    #[arg(
+        long,
+        requires = "recursive",
+        help = "Leave out files ignored by .gitignore files in the directories"
+    )]
+    pub gitignore: bool,
+    #[arg(
+        long,
+        requires = "recursive",
+        help = "Include files and directories whose names start with a dot"
+    )]
+    pub hidden: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Symlinks::Skip,
+        requires = "recursive",
+        help = "What to do with symbolic links in directories"
+    )]
+    pub symlinks: Symlinks,
 }
 
 #[derive(Debug, PartialEq, Args)]
@@ -41,7 +252,31 @@ pub struct CatboxArgs {
 pub struct Upload {
     #[arg(from_global)]
     pub user_hash: Option<String>,
-    #[arg(num_args(1..), help = "URLs or paths of the files to upload")]
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(
+        long,
+        help = "Name of the file read from stdin (-). Its extension is detected from the \
+                contents if missing. (Default: stdin)"
+    )]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub walk_args: WalkArgs,
+    #[arg(long, help = "Create an album with this title of the uploaded files")]
+    pub album: Option<String>,
+    #[arg(
+        short,
+        long,
+        alias = "desc",
+        requires = "album",
+        help = "Description of the album"
+    )]
+    pub description: Option<String>,
+    #[arg(
+        required = true,
+        num_args(1..),
+        help = "URLs or paths of the files to upload, or - for stdin"
+    )]
     pub files: Vec<String>,
 }
 
@@ -50,7 +285,9 @@ pub struct Upload {
 pub struct Delete {
     #[arg(from_global)]
     pub user_hash: Option<String>,
-    #[arg(num_args(1..), help = "IDs the files to delete")]
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(required = true, num_args(1..), help = "IDs or URLs of the files to delete")]
     pub files: Vec<String>,
 }
 
@@ -64,23 +301,65 @@ pub struct Album {
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Upload a temporary file to Litterbox. Max size 1GB.")]
 pub struct Litter {
-    #[arg(short, long, help = "Hours to keep the file", value_parser = valid_hour)]
-    pub time: Option<u8>,
-    #[arg(num_args(1..), help = "Paths of the files to upload")]
+    #[arg(
+        short,
+        long,
+        help = "Time to keep the file. Options: 1h, 12h, 24h/1d, 72h/3d (Default: the profile's \
+                litter_expiry or 1h)"
+    )]
+    pub time: Option<LitterExpiry>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(
+        long,
+        help = "Name of the file read from stdin (-). Its extension is detected from the \
+                contents if missing. (Default: stdin)"
+    )]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub walk_args: WalkArgs,
+    #[arg(
+        required = true,
+        num_args(1..),
+        help = "Paths of the files to upload, or - for stdin"
+    )]
     pub files: Vec<String>,
 }
 
-fn valid_hour(hour: &str) -> Result<u8, String> {
-    let hour: u8 = hour
-        .parse()
-        .map_err(|_| format!("{hour} is not a valid number"))?;
-    if [1, 12, 24, 72].contains(&hour) {
-        Ok(hour)
-    } else {
-        Err(format!(
-            "{hour} is not a valid value (Options: 1, 12, 24, 72"
-        ))
-    }
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Show the title, description and files of an album")]
+pub struct AlbumShow {
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(help = "Catbox ID or URL of the album")]
+    pub short: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Download files, or every file of albums")]
+pub struct Download {
+    #[arg(
+        short,
+        long,
+        default_value = ".",
+        help = "Directory to save the files in, created if missing"
+    )]
+    pub dir: PathBuf,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "What to do when a file of the same name exists"
+    )]
+    pub on_conflict: Conflict,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(
+        required = true,
+        num_args(1..),
+        help = "IDs or URLs of files on Catbox or Litterbox, or of albums"
+    )]
+    pub inputs: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Args)]
@@ -92,12 +371,14 @@ pub struct AlbumCreate {
     pub description: Option<String>,
     #[arg(from_global)]
     pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
     #[arg(num_args(1..), help = "Catbox IDs of the files to add to the album")]
     pub files: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Args)]
-#[command(about = "Edit an album")]
+#[command(about = "Edit an album, replacing its title, description and files")]
 pub struct AlbumEdit {
     #[arg(short, long, help = "Catbox ID of the album to edit")]
     pub short: String,
@@ -107,10 +388,50 @@ pub struct AlbumEdit {
     pub description: Option<String>,
     #[arg(from_global)]
     pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
     #[arg(num_args(1..), help = "Catbox IDs of the files the album should contain")]
     pub files: Vec<String>,
 }
 
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Change some of an album's title, description and files, keeping the rest",
+    group(ArgGroup::new("changes").required(true).multiple(true))
+)]
+pub struct AlbumUpdate {
+    #[arg(short, long, help = "Catbox ID of the album to update")]
+    pub short: String,
+    #[arg(short, long, group = "changes", help = "New title of the album")]
+    pub title: Option<String>,
+    #[arg(
+        short,
+        long,
+        alias = "desc",
+        group = "changes",
+        help = "New description of the album"
+    )]
+    pub description: Option<String>,
+    #[arg(
+        long,
+        num_args(1..),
+        group = "changes",
+        help = "Catbox IDs of files to add to the album"
+    )]
+    pub add: Vec<String>,
+    #[arg(
+        long,
+        num_args(1..),
+        group = "changes",
+        help = "Catbox IDs of files to remove from the album"
+    )]
+    pub remove: Vec<String>,
+    #[arg(from_global)]
+    pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Add files to an album")]
 pub struct AlbumAdd {
@@ -118,7 +439,9 @@ pub struct AlbumAdd {
     pub short: String,
     #[arg(from_global)]
     pub user_hash: Option<String>,
-    #[arg(num_args(1..), help = "Catbox IDs of the files to add to the album")]
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(required = true, num_args(1..), help = "Catbox IDs of the files to add to the album")]
     pub files: Vec<String>,
 }
 
@@ -129,7 +452,9 @@ pub struct AlbumRemove {
     pub short: String,
     #[arg(from_global)]
     pub user_hash: Option<String>,
-    #[arg(num_args(1..), help = "Catbox IDs of the files to remove from the album")]
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(required = true, num_args(1..), help = "Catbox IDs of the files to remove from the album")]
     pub files: Vec<String>,
 }
 
@@ -138,6 +463,183 @@ pub struct AlbumRemove {
 pub struct AlbumDelete {
     #[arg(from_global)]
     pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
     #[arg(help = "Catbox ID of the album to delete")]
     pub short: String,
 }
+
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Upload the new files of a directory and add them to an album",
+    long_about = "Upload the new files of a directory and add them to an album. The Catbox IDs \
+                  of uploaded files are kept in a state file, by default .catbox-sync.json in \
+                  the directory, so unchanged files are never uploaded again."
+)]
+pub struct AlbumSync {
+    #[arg(short, long, help = "Catbox ID of the album to sync")]
+    pub short: String,
+    #[arg(long, help = "Remove files from the album whose local files are gone")]
+    pub remove_missing: bool,
+    #[arg(
+        long,
+        help = "Remove files from the album whose local files are gone, and delete them from \
+                Catbox"
+    )]
+    pub delete_missing: bool,
+    #[arg(long, help = "Show what would be done without changing anything")]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "State file to use instead of .catbox-sync.json in the directory"
+    )]
+    pub state: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Only sync files matching this glob, e.g. '*.png'. Can be repeated."
+    )]
+    pub include: Vec<String>,
+    #[arg(
+        long,
+        help = "Leave out files and directories matching this glob. Can be repeated."
+    )]
+    pub exclude: Vec<String>,
+    #[arg(long, help = "Leave out files ignored by .gitignore files")]
+    pub gitignore: bool,
+    #[arg(
+        long,
+        help = "Include files and directories whose names start with a dot"
+    )]
+    pub hidden: bool,
+    #[arg(from_global)]
+    pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+    #[arg(help = "Directory to sync")]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Past uploads, albums and deletions")]
+pub struct History {
+    #[command(subcommand)]
+    pub history_command: HistoryCommand,
+}
+
+#[derive(Debug, PartialEq, Args)]
+pub struct HistoryFilter {
+    #[arg(long, help = "Only entries from this date on (YYYY-MM-DD)")]
+    pub since: Option<NaiveDate>,
+    #[arg(long, help = "Only entries up to and including this date (YYYY-MM-DD)")]
+    pub until: Option<NaiveDate>,
+    #[arg(
+        long,
+        help = "Only entries of this service. Options: catbox, litterbox"
+    )]
+    pub service: Option<Service>,
+    #[arg(long, help = "Only files whose original name contains this")]
+    pub name: Option<String>,
+    #[arg(
+        long,
+        help = "Only the creation and deletion of this album and uploads of the files it was created with"
+    )]
+    pub album: Option<AlbumShort>,
+    #[arg(from_global)]
+    pub user_hash: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "List recorded entries, oldest first")]
+pub struct HistoryList {
+    #[command(flatten)]
+    pub filter: HistoryFilter,
+    #[arg(short = 'n', long, help = "Only list this many of the latest entries")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "List entries with a path, name, URL or album title containing the query")]
+pub struct HistorySearch {
+    #[command(flatten)]
+    pub filter: HistoryFilter,
+    #[arg(help = "Text to search for, ignoring case")]
+    pub query: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Show all details of an entry")]
+pub struct HistoryShow {
+    #[arg(help = "Number of the entry, or the URL or ID of a file or album")]
+    pub entry: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Print recorded entries as JSON, CSV or Markdown")]
+pub struct HistoryExport {
+    #[command(flatten)]
+    pub filter: HistoryFilter,
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Json, help = "Format to export in")]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Save a user hash to the selected profile of the config file. Asks for it unless \
+             given with --user."
+)]
+pub struct Login {
+    #[arg(from_global)]
+    pub user_hash: Option<String>,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Remove the user hash from the selected profile of the config file")]
+pub struct Logout {
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Settings of the selected profile of the config file")]
+pub struct Config {
+    #[command(subcommand)]
+    pub config_command: ConfigCommand,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Print a setting")]
+pub struct ConfigGet {
+    #[arg(value_enum, help = "Setting to print")]
+    pub key: Key,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Change a setting")]
+pub struct ConfigSet {
+    #[arg(value_enum, help = "Setting to change")]
+    pub key: Key,
+    #[arg(help = "New value of the setting")]
+    pub value: String,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Remove a setting")]
+pub struct ConfigUnset {
+    #[arg(value_enum, help = "Setting to remove")]
+    pub key: Key,
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Print all settings of the selected profile")]
+pub struct ConfigList {
+    #[command(flatten)]
+    pub client_args: ClientArgs,
+}
@@ -0,0 +1,296 @@
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fmt::Display,
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use catbox::LitterExpiry;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{args::ClientArgs, output::OutputFormat};
+
+/// Name of the profile used when none is selected
+pub static DEFAULT_PROFILE: &str = "default";
+
+/// Contents of the config file
+///
+/// ```toml
+/// default_profile = "work"
+///
+/// [profiles.work]
+/// user_hash = "..."
+/// litter_expiry = "3d"
+/// retries = 2
+/// ```
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when `--profile` isn't given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings used unless given as flags or environment variables
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub user_hash: Option<String>,
+    pub api_url: Option<String>,
+    pub litter_api_url: Option<String>,
+    pub files_url: Option<String>,
+    pub album_url: Option<String>,
+    pub litter_expiry: Option<LitterExpiry>,
+    pub concurrency: Option<usize>,
+    pub retries: Option<u32>,
+    /// Request timeout in seconds
+    pub timeout: Option<u64>,
+    pub output: Option<OutputFormat>,
+}
+
+/// Setting of a profile, named like in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Key {
+    UserHash,
+    ApiUrl,
+    LitterApiUrl,
+    FilesUrl,
+    AlbumUrl,
+    LitterExpiry,
+    Concurrency,
+    Retries,
+    Timeout,
+    Output,
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.pad(value.get_name()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Profile {
+    /// Value of a setting, if it is set
+    pub fn get(&self, key: Key) -> Option<String> {
+        match key {
+            Key::UserHash => self.user_hash.clone(),
+            Key::ApiUrl => self.api_url.clone(),
+            Key::LitterApiUrl => self.litter_api_url.clone(),
+            Key::FilesUrl => self.files_url.clone(),
+            Key::AlbumUrl => self.album_url.clone(),
+            Key::LitterExpiry => self.litter_expiry.map(|expiry| expiry.to_string()),
+            Key::Concurrency => self.concurrency.map(|value| value.to_string()),
+            Key::Retries => self.retries.map(|value| value.to_string()),
+            Key::Timeout => self.timeout.map(|value| value.to_string()),
+            Key::Output => self.output.map(|output| output.to_string()),
+        }
+    }
+
+    /// Change a setting, or unset it if `value` is `None`
+    pub fn set(&mut self, key: Key, value: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let text = value.map(str::to_string);
+        match key {
+            Key::UserHash if value.is_some() => {
+                return Err(
+                    "The user hash isn't set on the command line. Use `catbox login` instead"
+                        .into(),
+                );
+            },
+            Key::UserHash => self.user_hash = text,
+            Key::ApiUrl => self.api_url = text,
+            Key::LitterApiUrl => self.litter_api_url = text,
+            Key::FilesUrl => self.files_url = text,
+            Key::AlbumUrl => self.album_url = text,
+            Key::LitterExpiry => self.litter_expiry = parse(key, value)?,
+            Key::Concurrency => self.concurrency = parse(key, value)?,
+            Key::Retries => self.retries = parse(key, value)?,
+            Key::Timeout => self.timeout = parse(key, value)?,
+            Key::Output => self.output = parse(key, value)?,
+        }
+        Ok(())
+    }
+}
+
+/// `value` with all but its last 4 characters hidden, for showing secrets
+pub fn masked(value: &str) -> String {
+    let shown = value.chars().count().saturating_sub(4);
+    value
+        .chars()
+        .enumerate()
+        .map(|(index, c)| match index < shown {
+            true => '*',
+            false => c,
+        })
+        .collect()
+}
+
+fn parse<T>(key: Key, value: Option<&str>) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| format!("Invalid value for {}: {}", key, err).into())
+        })
+        .transpose()
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/catbox/config.toml` or the platform's equivalent
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("catbox").join("config.toml"))
+    }
+
+    /// Read the config file. A missing file is an empty config.
+    ///
+    /// A file containing a user hash is refused if other users can read it.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let config = Self::read(path)?;
+        let has_user_hash = config
+            .profiles
+            .values()
+            .any(|profile| profile.user_hash.is_some());
+        if has_user_hash && readable_by_others(path)? {
+            return Err(format!(
+                "{} contains a user hash but can be read by other users. Run `chmod 600 {}` \
+                 or `catbox login` again.",
+                path.display(),
+                path.display()
+            )
+            .into());
+        }
+        Ok(config)
+    }
+
+    /// Read the config file without checking its permissions
+    fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| format!("Invalid config file {}: {}", path.display(), err).into()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err).into()),
+        }
+    }
+
+    /// Write the config file, readable only by the current user. Empty profiles are left out.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut config = Self {
+            default_profile: self.default_profile.clone(),
+            profiles: self.profiles.clone(),
+        };
+        config
+            .profiles
+            .retain(|_, profile| *profile != Profile::default());
+        let contents = toml::to_string_pretty(&config)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options
+            .open(path)
+            .map_err(|err| format!("Couldn't write {}: {}", path.display(), err))?;
+        // The mode only applies to new files
+        #[cfg(unix)]
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Select a profile by name, or the default one
+    ///
+    /// A missing default profile is empty, but a profile selected by name has to exist.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Box<dyn Error>> {
+        match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("No profile named {} in the config file", name).into()),
+            None => Ok(self
+                .profiles
+                .get(self.default_profile())
+                .cloned()
+                .unwrap_or_default()),
+        }
+    }
+
+    pub fn default_profile(&self) -> &str {
+        self.default_profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+}
+
+#[cfg(unix)]
+fn readable_by_others(path: &Path) -> Result<bool, io::Error> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.permissions().mode() & 0o044 != 0),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(not(unix))]
+fn readable_by_others(_path: &Path) -> Result<bool, io::Error> {
+    Ok(false)
+}
+
+/// Path of the config file: `--config`, `CATBOX_CONFIG` or the default path
+pub fn config_path(client_args: &ClientArgs) -> Option<PathBuf> {
+    client_args
+        .config
+        .clone()
+        .or_else(|| env::var_os("CATBOX_CONFIG").map(PathBuf::from))
+        .or_else(Config::default_path)
+}
+
+/// Name of the selected profile: `--profile` or `CATBOX_PROFILE`
+pub fn profile_name(client_args: &ClientArgs) -> Option<String> {
+    client_args
+        .profile
+        .clone()
+        .or_else(|| env::var("CATBOX_PROFILE").ok())
+}
+
+/// Load the profile selected by the arguments from the config file
+pub fn load_profile(client_args: &ClientArgs) -> Result<Profile, Box<dyn Error>> {
+    let config = match config_path(client_args) {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    config.profile(profile_name(client_args).as_deref())
+}
+
+/// Change the selected profile in the config file, creating both if needed
+///
+/// Returns the path of the config file and the name of the profile
+pub fn edit_profile(
+    client_args: &ClientArgs,
+    edit: impl FnOnce(&mut Profile) -> Result<(), Box<dyn Error>>,
+) -> Result<(PathBuf, String), Box<dyn Error>> {
+    let path = config_path(client_args).ok_or("No config directory for the config file")?;
+    // The permissions aren't checked, the file is made private when saving
+    let mut config = Config::read(&path)?;
+    let name = profile_name(client_args).unwrap_or_else(|| config.default_profile().to_string());
+    edit(config.profiles.entry(name.clone()).or_default())?;
+    config.save(&path)?;
+    Ok((path, name))
+}
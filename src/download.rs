@@ -0,0 +1,95 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use catbox::{AlbumShort, FileUrl, ParseIdError};
+use clap::ValueEnum;
+
+/// What to do when a downloaded file would be saved over another
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Conflict {
+    /// Keep the existing file and don't download
+    #[default]
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Save as name-1.ext, name-2.ext and so on
+    Rename,
+}
+
+/// What an input of `catbox download` refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File(FileUrl),
+    Album(AlbumShort),
+}
+
+impl Source {
+    /// Tell albums and files apart by the shape of their IDs and URLs: albums
+    /// are under `album_url` or `/c/` and have no extension
+    pub fn parse(input: &str, album_url: &str, files_url: &str) -> Result<Self, ParseIdError> {
+        AlbumShort::parse_with(input, album_url)
+            .map(Source::Album)
+            .or_else(|_| FileUrl::parse_with(input, files_url).map(Source::File))
+    }
+}
+
+/// Picks the paths downloaded files are saved to, so that no two downloads
+/// of a batch share one
+#[derive(Debug)]
+pub struct Targets {
+    dir: PathBuf,
+    conflict: Conflict,
+    taken: HashSet<PathBuf>,
+}
+
+impl Targets {
+    pub fn new(dir: &Path, conflict: Conflict) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            conflict,
+            taken: HashSet::new(),
+        }
+    }
+
+    /// Path to save `file` to, or the reason it isn't downloaded
+    ///
+    /// An unfinished download of the same name is resumed rather than
+    /// counted as a conflict.
+    pub fn path(&mut self, file: &FileUrl) -> Result<PathBuf, String> {
+        let name = file.id().as_str();
+        if name.trim_matches('.').is_empty() {
+            return Err("Not a valid file name".to_string());
+        }
+        let path = self.dir.join(name);
+        let path = match (self.taken.contains(&path), path.exists(), self.conflict) {
+            (false, false, _) | (false, true, Conflict::Overwrite) => path,
+            (true, _, Conflict::Skip) => {
+                return Err(format!("Another file is saved as {}", path.display()));
+            },
+            (false, true, Conflict::Skip) => {
+                return Err(format!(
+                    "{} exists. Use --on-conflict to overwrite or rename it.",
+                    path.display()
+                ));
+            },
+            (true, _, _) | (false, true, Conflict::Rename) => self.free_path(name),
+        };
+        self.taken.insert(path.clone());
+        Ok(path)
+    }
+
+    /// First of name-1.ext, name-2.ext and so on that neither exists nor is taken.
+    /// An interrupted download of a renamed file is saved as the first of them, so it's resumed.
+    fn free_path(&self, name: &str) -> PathBuf {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name, String::new()),
+        };
+        (1..)
+            .map(|n| self.dir.join(format!("{}-{}{}", stem, n, extension)))
+            .find(|path| !self.taken.contains(path) && !path.exists())
+            .unwrap_or_else(|| self.dir.join(name))
+    }
+}
@@ -0,0 +1,108 @@
+use std::{error::Error, fmt, process::ExitCode};
+
+use catbox::CatboxError;
+
+use crate::output::{Report, Status};
+
+/// Exit codes of the binary other than 0 for success
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The command failed, or every input of a batch did
+    Failure = 1,
+    /// Invalid arguments, set by clap
+    Usage = 2,
+    /// Some inputs of a batch failed and others succeeded
+    Partial = 3,
+    /// The command needs a user hash and none was given, or Catbox refused it
+    Auth = 4,
+    /// The server couldn't be reached
+    Network = 5,
+}
+
+/// Replies of Catbox refusing a request for the user hash it was made with,
+/// e.g. to change an album of another user
+static AUTH_REJECTIONS: &[&str] = &["No album found for user specified."];
+
+/// Error ending the command with a specific exit code
+#[derive(Debug)]
+pub struct ExitError {
+    pub exit: Exit,
+    /// Printed to stderr, unless the failure was already reported
+    pub message: Option<String>,
+}
+
+impl ExitError {
+    pub fn new(exit: Exit, message: impl Into<String>) -> Self {
+        Self {
+            exit,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Error for a failure that has already been printed
+    pub fn silent(exit: Exit) -> Self {
+        Self {
+            exit,
+            message: None,
+        }
+    }
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message.as_deref().unwrap_or_default())
+    }
+}
+
+impl Error for ExitError {}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+/// Exit code for an error returned by a command
+pub fn exit_code(err: &(dyn Error + 'static)) -> Exit {
+    if let Some(err) = err.downcast_ref::<ExitError>() {
+        return err.exit;
+    }
+    match err.downcast_ref::<CatboxError>() {
+        Some(err) => error_exit(err),
+        None => Exit::Failure,
+    }
+}
+
+/// Exit code for a failed request
+pub fn error_exit(err: &CatboxError) -> Exit {
+    match err {
+        CatboxError::Http(_) => Exit::Network,
+        CatboxError::Api(message) if AUTH_REJECTIONS.contains(&message.as_str()) => Exit::Auth,
+        _ => Exit::Failure,
+    }
+}
+
+/// Whether the command succeeded for every input of a batch
+///
+/// Skipped inputs count as neither failed nor succeeded, so a batch whose
+/// inputs were all skipped succeeds.
+pub fn check_batch(reports: &[Report]) -> Result<(), ExitError> {
+    let failed: Vec<_> = reports
+        .iter()
+        .filter(|report| report.status == Status::Failed)
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let succeeded = reports.iter().any(|report| report.status == Status::Ok);
+    let exit = failed[0].exit();
+    let exit = match succeeded {
+        true => Exit::Partial,
+        false if failed.iter().all(|report| report.exit() == exit) => exit,
+        false => Exit::Failure,
+    };
+    Err(ExitError::new(
+        exit,
+        format!("{} of {} inputs failed", failed.len(), reports.len()),
+    ))
+}
@@ -0,0 +1,215 @@
+use catbox::history::{Action, Entry, Filter};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::args::{ExportFormat, HistoryFilter};
+
+static CSV_HEADER: &[&str] = &[
+    "number", "time", "action", "service", "source", "name", "size", "sha256", "url", "expires",
+    "title", "album", "files",
+];
+
+/// Filter matching the command line arguments. Dates are in local time.
+pub fn filter(args: HistoryFilter, text: Option<String>) -> Filter {
+    Filter {
+        since: args.since.and_then(start_of_day),
+        until: args
+            .until
+            .and_then(|until| until.succ_opt())
+            .and_then(start_of_day),
+        service: args.service,
+        name: args.name,
+        album: args.album,
+        user_hash: args.user_hash,
+        text,
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Find an entry by its number, or the last one with a matching URL or album
+pub fn find<'a>(entries: &'a [(usize, Entry)], key: &str) -> Option<&'a (usize, Entry)> {
+    if let Ok(number) = key.parse::<usize>() {
+        return entries.iter().find(|(n, _)| *n == number);
+    }
+    let key = key.trim().trim_end_matches('/');
+    entries.iter().rev().find(|(_, entry)| {
+        entry
+            .url
+            .as_deref()
+            .is_some_and(|url| url == key || url.rsplit('/').next() == Some(key))
+            || entry.album.as_deref() == Some(key)
+    })
+}
+
+/// One line per entry with its number, time, service and what was done
+pub fn list(entries: &[(usize, Entry)]) -> String {
+    entries
+        .iter()
+        .map(|(number, entry)| {
+            format!(
+                "{:>4}  {}  {:<9}  {}",
+                number,
+                local_time(&entry.time),
+                entry.service(),
+                summary(entry)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn summary(entry: &Entry) -> String {
+    let url = entry.url.as_deref().unwrap_or_default();
+    match entry.action {
+        Action::Upload | Action::UrlUpload | Action::LitterUpload => {
+            let mut summary = format!(
+                "{}  {}",
+                url,
+                entry
+                    .source
+                    .as_deref()
+                    .or(entry.name.as_deref())
+                    .unwrap_or_default()
+            );
+            if let Some(expires) = &entry.expires {
+                summary += &format!("  (expires {})", local_time(expires));
+            }
+            summary
+        },
+        Action::Delete => format!("Deleted {}", entry.files.join(" ")),
+        Action::AlbumCreate => format!(
+            "{}  Album \"{}\" with {} files",
+            url,
+            entry.title.as_deref().unwrap_or_default(),
+            entry.files.len()
+        ),
+        Action::AlbumDelete => format!(
+            "Deleted album {}",
+            entry.album.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+/// All recorded details of an entry, one per line
+pub fn show(number: usize, entry: &Entry) -> String {
+    let mut lines = vec![
+        format!("Entry:     {}", number),
+        format!("Time:      {}", local_time(&entry.time)),
+        format!("Action:    {}", entry.action),
+        format!("Service:   {}", entry.service()),
+    ];
+    let mut field = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            lines.push(format!("{:<10} {}", format!("{}:", name), value));
+        }
+    };
+    field("Source", entry.source.clone());
+    field("Name", entry.name.clone());
+    field("Size", entry.size.map(|size| format!("{} bytes", size)));
+    field("SHA-256", entry.sha256.clone());
+    field("URL", entry.url.clone());
+    field("Expires", entry.expires.as_ref().map(local_time));
+    field("Title", entry.title.clone());
+    field("Album", entry.album.clone());
+    field(
+        "Files",
+        (!entry.files.is_empty()).then(|| entry.files.join(" ")),
+    );
+    lines.join("\n")
+}
+
+/// Entries in the given format. User hashes are left out, as they let anyone
+/// edit and delete the uploads.
+pub fn export(
+    entries: &[(usize, Entry)],
+    format: ExportFormat,
+) -> Result<String, serde_json::Error> {
+    Ok(match format {
+        ExportFormat::Json => serde_json::to_string_pretty(
+            &entries
+                .iter()
+                .map(|(_, entry)| Entry {
+                    user_hash: None,
+                    ..entry.clone()
+                })
+                .collect::<Vec<_>>(),
+        )?,
+        ExportFormat::Csv => {
+            let mut lines = vec![CSV_HEADER.join(",")];
+            lines.extend(
+                entries
+                    .iter()
+                    .map(|(number, entry)| fields(*number, entry).map(csv_field).join(",")),
+            );
+            lines.join("\n")
+        },
+        ExportFormat::Markdown => {
+            let mut lines = vec![
+                "| Time | Service | Action | File | URL | Size | Expires |".to_string(),
+                "| --- | --- | --- | --- | --- | --- | --- |".to_string(),
+            ];
+            lines.extend(entries.iter().map(|(_, entry)| {
+                let file = entry
+                    .source
+                    .as_deref()
+                    .or(entry.name.as_deref())
+                    .or(entry.title.as_deref())
+                    .unwrap_or_default();
+                let cells = [
+                    local_time(&entry.time),
+                    entry.service().to_string(),
+                    entry.action.to_string(),
+                    file.to_string(),
+                    entry.url.clone().unwrap_or_default(),
+                    entry.size.map(|size| size.to_string()).unwrap_or_default(),
+                    entry.expires.as_ref().map(local_time).unwrap_or_default(),
+                ];
+                format!(
+                    "| {} |",
+                    cells.map(|cell| cell.replace('|', "\\|")).join(" | ")
+                )
+            }));
+            lines.join("\n")
+        },
+    })
+}
+
+fn fields(number: usize, entry: &Entry) -> [String; 13] {
+    [
+        number.to_string(),
+        entry.time.to_rfc3339(),
+        entry.action.to_string(),
+        entry.service().to_string(),
+        entry.source.clone().unwrap_or_default(),
+        entry.name.clone().unwrap_or_default(),
+        entry.size.map(|size| size.to_string()).unwrap_or_default(),
+        entry.sha256.clone().unwrap_or_default(),
+        entry.url.clone().unwrap_or_default(),
+        entry
+            .expires
+            .map(|expires| expires.to_rfc3339())
+            .unwrap_or_default(),
+        entry.title.clone().unwrap_or_default(),
+        entry.album.clone().unwrap_or_default(),
+        entry.files.join(" "),
+    ]
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: String) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field,
+    }
+}
+
+fn local_time(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
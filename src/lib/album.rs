@@ -1,12 +1,491 @@
 //! Functions for handling albums through Catbox's API
 //!
-//! Calls API described at <https://catbox.moe/tools.php>.
+//! Calls API described at <https://catbox.moe/tools.php>. The API can't list
+//! the contents of an album, so those are read from its public page.
 
-use std::error::Error;
+use std::future::Future;
 
-use reqwest::Client;
+use futures::{StreamExt, stream};
+use url::Url;
 
-use super::{CATBOX_API_URL, UASTRING};
+use super::{
+    AlbumShort, AlbumUrl, CatboxClient, CatboxError, FileId, FileUrl,
+    history::{Action, Entry},
+    page, response, types,
+};
+
+/// Files uploaded at the same time by `create_from_files`
+const UPLOAD_CONCURRENCY: usize = 4;
+/// Extensions of links to pages rather than files, e.g. the FAQ
+const PAGE_EXTENSIONS: &[&str] = &["php", "html", "htm", "asp", "aspx", "jsp", "cgi"];
+/// Prefix of the `<title>` of album pages
+const PAGE_TITLE_PREFIX: &str = "Catbox Album:";
+
+/// Contents of an album, read from its public page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Album {
+    /// URL of the album
+    pub url: AlbumUrl,
+    /// Album title
+    pub title: String,
+    /// Album description, empty if it has none
+    pub description: String,
+    /// Files in the album, in the order shown on the page
+    pub files: Vec<FileUrl>,
+    /// Links on the page that look like files but not under the files URL,
+    /// e.g. on another host. Any of them may be a file missing from `files`.
+    pub unrecognized: Vec<String>,
+}
+
+impl Album {
+    /// Parse the public page of an album
+    ///
+    /// Returns the album, or an error if the page isn't one of an album
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL the page was read from
+    /// * `html` - Contents of the page
+    /// * `files_url` - Base URL of the files, e.g. `https://files.catbox.moe/`
+    pub fn parse(url: AlbumUrl, html: &str, files_url: &str) -> Result<Self, CatboxError> {
+        let title = meta(html, "og:title")
+            .or_else(|| {
+                page::element_text(html, "title")?
+                    .strip_prefix(PAGE_TITLE_PREFIX)
+                    .map(|title| title.trim().to_string())
+            })
+            .ok_or_else(|| CatboxError::Api(format!("No album found at {}", url)))?;
+        let description = meta(html, "og:description").unwrap_or_default();
+
+        let page_url = Url::parse(url.as_str()).ok();
+        let mut files: Vec<FileUrl> = Vec::new();
+        let mut unrecognized = Vec::new();
+        let links = page::tags(html, "a").filter_map(|tag| page::attribute(tag, "href"));
+        for link in links {
+            // Resolves relative and protocol-relative links
+            let Some(target) = page_url.as_ref().and_then(|page| page.join(&link).ok()) else {
+                continue;
+            };
+            match types::file_under(&target, files_url) {
+                Some(id) => {
+                    let file = FileUrl::new(files_url, id);
+                    if !files.contains(&file) {
+                        files.push(file);
+                    }
+                },
+                None if looks_like_file(&target) => unrecognized.push(link),
+                None => {},
+            }
+        }
+        Ok(Self {
+            url,
+            title,
+            description,
+            files,
+            unrecognized,
+        })
+    }
+}
+
+/// Changes made to an album by `CatboxClient::update_album`
+///
+/// Fields left as `None` or empty keep their current values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlbumChanges {
+    /// New title
+    pub title: Option<String>,
+    /// New description
+    pub description: Option<String>,
+    /// Files to add after the current ones, unless already in the album
+    pub add: Vec<FileId>,
+    /// Files to remove from the album
+    pub remove: Vec<FileId>,
+}
+
+impl AlbumChanges {
+    /// Whether applying the changes would leave any album as it is
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.add.is_empty()
+            && self.remove.is_empty()
+    }
+}
+
+/// Whether `target` looks like a file rather than a page
+fn looks_like_file(target: &Url) -> bool {
+    let name = target
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+    ["http", "https"].contains(&target.scheme())
+        && name.parse::<FileId>().is_ok()
+        && !PAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+}
+
+/// Content of the `<meta>` tag with the property or name `property`
+fn meta(html: &str, property: &str) -> Option<String> {
+    page::tags(html, "meta")
+        .find(|tag| {
+            page::attribute(tag, "property")
+                .or_else(|| page::attribute(tag, "name"))
+                .is_some_and(|name| name == property)
+        })
+        .and_then(|tag| page::attribute(tag, "content"))
+}
+
+/// Album created with `CatboxClient::create_album_from_files`
+/// or `CatboxClient::create_album_from_uploads`
+#[derive(Debug)]
+pub struct AlbumUpload<T = String> {
+    /// URL of the created album
+    pub url: AlbumUrl,
+    /// Uploaded files in the album, in the order of their inputs
+    pub files: Vec<FileUrl>,
+    /// Inputs that couldn't be uploaded and were left out of the album,
+    /// with the reasons
+    pub failed: Vec<(T, CatboxError)>,
+}
+
+impl CatboxClient {
+    /// Read the title, description and files of an album from its public page
+    ///
+    /// Returns the contents of the album
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    pub async fn get_album(&self, short: AlbumShort) -> Result<Album, CatboxError> {
+        let url = AlbumUrl::new(&self.album_url, short);
+        let html = self
+            .with_retries(|| async {
+                let response = self.http.get(url.as_str()).send().await?;
+                response::text(response).await
+            })
+            .await?;
+        Album::parse(url, &html, &self.files_url)
+    }
+
+    /// Create a new album
+    ///
+    /// The album can only be edited or deleted later if the client has a user hash.
+    ///
+    /// Returns an URL to the created album
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Album title
+    /// * `desc` - Album description
+    /// * `files` - List of existing files on Catbox to be added to the album
+    pub async fn create_album<S: Into<String>>(
+        &self,
+        title: S,
+        desc: S,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let title = title.into();
+        let files: Vec<_> = files.iter().map(FileId::as_str).collect();
+        let form = [
+            ("reqtype", "createalbum"),
+            ("userhash", &self.user_hash_or_default()),
+            ("title", &title),
+            ("desc", &desc.into()),
+            ("files", &files.join(" ")),
+        ];
+        let url: AlbumUrl = self
+            .post_form_once(&self.catbox_api_url, &form, |body| {
+                self.expect_album_url(body)
+            })
+            .await?;
+        let mut entry = Entry::new(Action::AlbumCreate);
+        entry.url = Some(url.to_string());
+        entry.album = Some(url.short().to_string());
+        entry.title = Some(title);
+        entry.files = files.iter().map(|file| file.to_string()).collect();
+        self.record(entry).await;
+        Ok(url)
+    }
+
+    /// Upload local files and create a new album of them
+    ///
+    /// Files that fail to upload are left out of the album. If none of them
+    /// can be uploaded, no album is created.
+    ///
+    /// Returns the URL of the album, the uploaded files in the order of `file_paths`
+    /// and the files that were left out, or the first error if no file was uploaded
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Album title
+    /// * `desc` - Album description
+    /// * `file_paths` - Paths to the files
+    /// * `concurrency` - Number of files to upload at the same time
+    pub async fn create_album_from_files<S: Into<String>>(
+        &self,
+        title: S,
+        desc: S,
+        file_paths: Vec<String>,
+        concurrency: usize,
+    ) -> Result<AlbumUpload, CatboxError> {
+        self.create_album_from_uploads(title, desc, file_paths, concurrency, |path| {
+            self.upload_file(path)
+        })
+        .await
+    }
+
+    /// Upload inputs with `upload` and create a new album of them
+    ///
+    /// Like `create_album_from_files`, for inputs that aren't only local files,
+    /// e.g. URLs, or files whose upload progress is shown.
+    ///
+    /// Returns the URL of the album, the uploaded files in the order of `inputs`
+    /// and the inputs that were left out, or the first error if nothing was uploaded
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Album title
+    /// * `desc` - Album description
+    /// * `inputs` - What to upload
+    /// * `concurrency` - Number of inputs to upload at the same time
+    /// * `upload` - Uploads an input, returning the URL of the file
+    pub async fn create_album_from_uploads<T, S, F, Fut>(
+        &self,
+        title: S,
+        desc: S,
+        inputs: Vec<T>,
+        concurrency: usize,
+        upload: F,
+    ) -> Result<AlbumUpload<T>, CatboxError>
+    where
+        T: Clone,
+        S: Into<String>,
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = Result<FileUrl, CatboxError>>,
+    {
+        let upload = &upload;
+        let mut uploads: Vec<_> = stream::iter(inputs.into_iter().enumerate().map(
+            |(index, input)| async move {
+                let res = upload(input.clone()).await;
+                (index, input, res)
+            },
+        ))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+        uploads.sort_by_key(|(index, ..)| *index);
+
+        let mut files = Vec::new();
+        let mut failed = Vec::new();
+        for (_, input, res) in uploads {
+            match res {
+                Ok(url) => files.push(url),
+                Err(err) => failed.push((input, err)),
+            }
+        }
+        if files.is_empty() && !failed.is_empty() {
+            return Err(failed.swap_remove(0).1);
+        }
+        let ids = files.iter().map(|url| url.id().clone()).collect();
+        let url = self.create_album(title, desc, ids).await?;
+        Ok(AlbumUpload { url, files, failed })
+    }
+
+    /// Change some of an album's title, description and files, keeping the rest
+    ///
+    /// Reads the album's current contents from its page, then adds and removes
+    /// files with `add_to_album` and `remove_from_album`. A new title or description
+    /// is set with `edit_album`, which needs all of the files. It's refused with
+    /// `CatboxError::IncompleteAlbum`, before anything is changed, if the page
+    /// links to files that couldn't be read. Nothing is sent if there is nothing to change.
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns the album as it is after the changes
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    /// * `changes` - What to change
+    pub async fn update_album(
+        &self,
+        short: AlbumShort,
+        changes: AlbumChanges,
+    ) -> Result<Album, CatboxError> {
+        let mut album = self.get_album(short.clone()).await?;
+        let edit = changes.title.is_some() || changes.description.is_some();
+        if edit && !album.unrecognized.is_empty() {
+            return Err(CatboxError::IncompleteAlbum {
+                url: album.url.to_string(),
+                links: album.unrecognized,
+            });
+        }
+
+        let current: Vec<FileId> = album.files.iter().map(|file| file.id().clone()).collect();
+        let mut add: Vec<FileId> = Vec::new();
+        for id in changes.add {
+            if !current.contains(&id) && !add.contains(&id) {
+                add.push(id);
+            }
+        }
+        let mut remove: Vec<FileId> = Vec::new();
+        for id in changes.remove {
+            if !remove.contains(&id) {
+                remove.push(id);
+            }
+        }
+
+        if edit {
+            let title = changes.title.unwrap_or(album.title);
+            let description = changes.description.unwrap_or(album.description);
+            album.url = self
+                .edit_album(short.clone(), title.clone(), description.clone(), current)
+                .await?;
+            album.title = title;
+            album.description = description;
+        }
+        if !add.is_empty() {
+            album.url = self.add_to_album(short.clone(), add.clone()).await?;
+            album
+                .files
+                .extend(add.into_iter().map(|id| FileUrl::new(&self.files_url, id)));
+        }
+        if !remove.is_empty() {
+            album.url = self.remove_from_album(short, remove.clone()).await?;
+            album.files.retain(|file| !remove.contains(file.id()));
+        }
+        Ok(album)
+    }
+
+    /// Edit an album
+    ///
+    /// **NOTE:** Old album will be "overwritten" with the new information.
+    /// Include everything you want the album to have in the call, or use
+    /// `update_album` to change only some of it.
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns an URL to the album
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    /// * `title` - Album title
+    /// * `desc` - Album description
+    /// * `files` - List of existing files on Catbox to be included in the album
+    pub async fn edit_album<S: Into<String>>(
+        &self,
+        short: AlbumShort,
+        title: S,
+        desc: S,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let files: Vec<_> = files.iter().map(FileId::as_str).collect();
+        let form = [
+            ("reqtype", "editalbum"),
+            ("userhash", &self.user_hash_or_default()),
+            ("short", short.as_str()),
+            ("title", &title.into()),
+            ("desc", &desc.into()),
+            ("files", &files.join(" ")),
+        ];
+        self.post_form(&self.catbox_api_url, &form, |body| {
+            self.expect_album_url(body)
+        })
+        .await
+    }
+
+    /// Add files to an album
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns an URL to the album
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    /// * `files` - List of existing files on Catbox to be added to the album
+    pub async fn add_to_album(
+        &self,
+        short: AlbumShort,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let files: Vec<_> = files.iter().map(FileId::as_str).collect();
+        let form = [
+            ("reqtype", "addtoalbum"),
+            ("short", short.as_str()),
+            ("userhash", &self.user_hash_or_default()),
+            ("files", &files.join(" ")),
+        ];
+        self.post_form_once(&self.catbox_api_url, &form, |body| {
+            self.expect_album_url(body)
+        })
+        .await
+    }
+
+    /// Remove files from an album
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns an URL to the album
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    /// * `files` - List of existing files on Catbox to be removed from the album
+    pub async fn remove_from_album(
+        &self,
+        short: AlbumShort,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let files: Vec<_> = files.iter().map(FileId::as_str).collect();
+        let form = [
+            ("reqtype", "removefromalbum"),
+            ("userhash", &self.user_hash_or_default()),
+            ("short", short.as_str()),
+            ("files", &files.join(" ")),
+        ];
+        self.post_form_once(&self.catbox_api_url, &form, |body| {
+            self.expect_album_url(body)
+        })
+        .await
+    }
+
+    /// Delete an album
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns an empty string
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - ID of the album
+    pub async fn delete_album(&self, short: AlbumShort) -> Result<String, CatboxError> {
+        let form = [
+            ("reqtype", "deletealbum"),
+            ("userhash", &self.user_hash_or_default()),
+            ("short", short.as_str()),
+        ];
+        let res = self
+            .post_form_once(&self.catbox_api_url, &form, response::expect_empty)
+            .await?;
+        let mut entry = Entry::new(Action::AlbumDelete);
+        entry.album = Some(short.to_string());
+        self.record(entry).await;
+        Ok(res)
+    }
+}
+
+/// Read the title, description and files of an album from its public page
+///
+/// Returns the contents of the album
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+pub async fn get(short: AlbumShort) -> Result<Album, CatboxError> {
+    CatboxClient::new().get_album(short).await
+}
 
 /// Create a new album
 ///
@@ -22,37 +501,62 @@ pub async fn create<S: Into<String>>(
     title: S,
     desc: S,
     user_hash: Option<S>,
-    files: Vec<S>,
-) -> Result<String, Box<dyn Error>> {
-    let files: Vec<_> = files.into_iter().map(|file| file.into()).collect();
-    let form = [
-        ("reqtype", "createalbum"),
-        (
-            "userhash",
-            &user_hash
-                .and_then(|hash| Some(hash.into()))
-                .unwrap_or_default(),
-        ),
-        ("title", &title.into()),
-        ("desc", &desc.into()),
-        ("files", &files.join(" ")),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+    files: Vec<FileId>,
+) -> Result<AlbumUrl, CatboxError> {
+    CatboxClient::from_user_hash(user_hash)
+        .create_album(title, desc, files)
+        .await
+}
+
+/// Upload local files and create a new album of them
+///
+/// Files that fail to upload are left out of the album. If none of them
+/// can be uploaded, no album is created.
+///
+/// Returns the URL of the album, the uploaded files in the order of `file_paths`
+/// and the files that were left out, or the first error if no file was uploaded
+///
+/// # Arguments
+///
+/// * `title` - Album title
+/// * `desc` - Album description
+/// * `user_hash` - User's account hash, required for deleting or editing. (Optional)
+/// * `file_paths` - Paths to the files
+pub async fn create_from_files<S: Into<String>>(
+    title: S,
+    desc: S,
+    user_hash: Option<S>,
+    file_paths: Vec<String>,
+) -> Result<AlbumUpload, CatboxError> {
+    CatboxClient::from_user_hash(user_hash)
+        .create_album_from_files(title, desc, file_paths, UPLOAD_CONCURRENCY)
+        .await
+}
+
+/// Change some of an album's title, description and files, keeping the rest
+///
+/// Returns the album as it is after the changes
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `user_hash` - User's account hash
+/// * `changes` - What to change
+pub async fn update<S: Into<String>>(
+    short: AlbumShort,
+    user_hash: S,
+    changes: AlbumChanges,
+) -> Result<Album, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .update_album(short, changes)
+        .await
 }
 
 /// Edit an album
 ///
 /// **NOTE:** Old album will be "overwritten" with the new information.
-/// Include everything you want the album to have in the call.
+/// Include everything you want the album to have in the call, or use
+/// `update` to change only some of it.
 ///
 /// Returns an URL to the album
 ///
@@ -64,31 +568,15 @@ pub async fn create<S: Into<String>>(
 /// * `files` - List of existing files on Catbox to be included in the album
 /// * `user_hash` - User's account hash
 pub async fn edit<S: Into<String>>(
-    short: S,
+    short: AlbumShort,
     title: S,
     desc: S,
     user_hash: S,
-    files: Vec<S>,
-) -> Result<String, Box<dyn Error>> {
-    let files: Vec<_> = files.into_iter().map(|file| file.into()).collect();
-    let form = [
-        ("reqtype", "editalbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
-        ("title", &title.into()),
-        ("desc", &desc.into()),
-        ("files", &files.join(" ")),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+    files: Vec<FileId>,
+) -> Result<AlbumUrl, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .edit_album(short, title, desc, files)
+        .await
 }
 
 /// Add files to an album
@@ -101,27 +589,13 @@ pub async fn edit<S: Into<String>>(
 /// * `user_hash` - User's account hash
 /// * `files` - List of existing files on Catbox to be added to the album
 pub async fn add_files<S: Into<String>>(
-    short: S,
+    short: AlbumShort,
     user_hash: S,
-    files: Vec<S>,
-) -> Result<String, Box<dyn Error>> {
-    let files: Vec<_> = files.into_iter().map(|file| file.into()).collect();
-    let form = [
-        ("reqtype", "addtoalbum"),
-        ("short", &short.into()),
-        ("userhash", &user_hash.into()),
-        ("files", &files.join(" ")),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+    files: Vec<FileId>,
+) -> Result<AlbumUrl, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .add_to_album(short, files)
+        .await
 }
 
 /// Remove files from an album
@@ -134,27 +608,13 @@ pub async fn add_files<S: Into<String>>(
 /// * `user_hash` - User's account hash
 /// * `files` - List of existing files on Catbox to be removed from the album
 pub async fn remove_files<S: Into<String>>(
-    short: S,
+    short: AlbumShort,
     user_hash: S,
-    files: Vec<S>,
-) -> Result<String, Box<dyn Error>> {
-    let files: Vec<_> = files.into_iter().map(|file| file.into()).collect();
-    let form = [
-        ("reqtype", "removefromalbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
-        ("files", &files.join(" ")),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+    files: Vec<FileId>,
+) -> Result<AlbumUrl, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .remove_from_album(short, files)
+        .await
 }
 
 /// Delete an album
@@ -165,20 +625,349 @@ pub async fn remove_files<S: Into<String>>(
 ///
 /// * `short` - ID of the album
 /// * `user_hash` - User's account hash
-pub async fn delete<S: Into<String>>(short: S, user_hash: S) -> Result<String, Box<dyn Error>> {
-    let form = [
-        ("reqtype", "deletealbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+pub async fn delete<S: Into<String>>(
+    short: AlbumShort,
+    user_hash: S,
+) -> Result<String, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .delete_album(short)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ok, record, serve};
+
+    static FILES_URL: &str = "https://files.catbox.moe/";
+
+    fn album_url() -> AlbumUrl {
+        "https://catbox.moe/c/xyz789".parse().unwrap()
+    }
+
+    #[test]
+    fn parse_album_page() {
+        let album = Album::parse(
+            album_url(),
+            include_str!("../../tests/fixtures/album.html"),
+            FILES_URL,
+        )
+        .unwrap();
+        assert_eq!(album.url, album_url());
+        assert_eq!(album.title, "Cats & dogs");
+        assert_eq!(
+            album.description,
+            "Pictures from the weekend.\nTaken with my \"new\" camera"
+        );
+        let files: Vec<_> = album.files.iter().map(|file| file.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                "https://files.catbox.moe/abc123.png",
+                "https://files.catbox.moe/def456.jpg",
+                "https://files.catbox.moe/ghi789.mp4",
+                "https://files.catbox.moe/jkl012.zip",
+            ]
+        );
+        assert!(album.unrecognized.is_empty());
+    }
+
+    #[test]
+    fn parse_album_page_links() {
+        let page = include_str!("../../tests/fixtures/album.html")
+            .replace(
+                "https://files.catbox.moe/abc123.png\" target",
+                "http://files.catbox.moe/abc123.png\" target",
+            )
+            .replace(
+                "https://files.catbox.moe/def456.jpg\" target",
+                "//files.catbox.moe/def456.jpg\" target",
+            )
+            .replace(
+                "https://files.catbox.moe/ghi789.mp4\" target",
+                "https://cdn.example.com/ghi789.mp4\" target",
+            );
+        let album = Album::parse(album_url(), &page, FILES_URL).unwrap();
+        let files: Vec<_> = album.files.iter().map(|file| file.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                "https://files.catbox.moe/abc123.png",
+                "https://files.catbox.moe/def456.jpg",
+                "https://files.catbox.moe/jkl012.zip",
+            ]
+        );
+        assert_eq!(album.unrecognized, ["https://cdn.example.com/ghi789.mp4"]);
+
+        let page = include_str!("../../tests/fixtures/album.html")
+            .replace("https://files.catbox.moe/", "/files/");
+        let album = Album::parse(
+            "https://example.com/c/xyz789".parse().unwrap(),
+            &page,
+            "https://example.com/files",
+        )
+        .unwrap();
+        assert_eq!(album.files.len(), 4);
+        assert_eq!(
+            album.files[0].as_str(),
+            "https://example.com/files/abc123.png"
+        );
+        assert!(album.unrecognized.is_empty());
+    }
+
+    #[test]
+    fn parse_empty_album_page() {
+        let album = Album::parse(
+            album_url(),
+            include_str!("../../tests/fixtures/album_empty.html"),
+            FILES_URL,
+        )
+        .unwrap();
+        assert_eq!(album.title, "Nothing yet");
+        assert_eq!(album.description, "");
+        assert!(album.files.is_empty());
+    }
+
+    #[test]
+    fn parse_missing_album_page() {
+        assert!(matches!(
+            Album::parse(
+                album_url(),
+                include_str!("../../tests/fixtures/album_missing.html"),
+                FILES_URL,
+            ),
+            Err(CatboxError::Api(_))
+        ));
+    }
+
+    /// Apply `changes` to the album in `page`, answering every request sent
+    /// after reading the page with the album's URL
+    ///
+    /// Returns the result and the bodies of the requests sent after reading the page
+    async fn update(
+        page: &str,
+        changes: AlbumChanges,
+    ) -> (Result<Album, CatboxError>, Vec<String>) {
+        let mut responses = vec![ok(page)];
+        responses.extend((0..3).map(|_| ok("https://catbox.moe/c/xyz789")));
+        let (url, mut requests) = record(responses).await;
+        let client = CatboxClient::builder()
+            .album_url(url.clone())
+            .catbox_api_url(url)
+            .user_hash("hash123")
+            .build();
+        let res = client
+            .update_album("xyz789".parse().unwrap(), changes)
+            .await;
+        let page_request = requests.recv().await.unwrap();
+        assert_eq!(page_request.line(), "GET /xyz789 HTTP/1.1");
+        let mut forms = Vec::new();
+        while let Ok(request) = requests.try_recv() {
+            forms.push(String::from_utf8(request.body).unwrap());
+        }
+        (res, forms)
+    }
+
+    fn ids(ids: &[&str]) -> Vec<FileId> {
+        ids.iter().map(|id| id.parse().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn update_album() {
+        let page = include_str!("../../tests/fixtures/album.html");
+
+        let (album, forms) = update(
+            page,
+            AlbumChanges {
+                title: Some("Dogs".to_string()),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        assert_eq!(album.unwrap().title, "Dogs");
+        assert_eq!(
+            forms,
+            ["reqtype=editalbum&userhash=hash123&short=xyz789&title=Dogs\
+              &desc=Pictures+from+the+weekend.%0ATaken+with+my+%22new%22+camera\
+              &files=abc123.png+def456.jpg+ghi789.mp4+jkl012.zip"]
+        );
+
+        let (album, forms) = update(
+            page,
+            AlbumChanges {
+                add: ids(&["new111.gif", "abc123.png", "new111.gif"]),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        assert_eq!(album.unwrap().files.len(), 5);
+        assert_eq!(
+            forms,
+            ["reqtype=addtoalbum&short=xyz789&userhash=hash123&files=new111.gif"]
+        );
+
+        let (album, forms) = update(
+            page,
+            AlbumChanges {
+                remove: ids(&["def456.jpg"]),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        assert_eq!(album.unwrap().files.len(), 3);
+        assert_eq!(
+            forms,
+            ["reqtype=removefromalbum&userhash=hash123&short=xyz789&files=def456.jpg"]
+        );
+
+        let (album, forms) = update(
+            page,
+            AlbumChanges {
+                description: Some(String::new()),
+                add: ids(&["new111.gif"]),
+                remove: ids(&["def456.jpg"]),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        let album = album.unwrap();
+        assert_eq!(album.title, "Cats & dogs");
+        assert_eq!(album.description, "");
+        let files: Vec<_> = album.files.iter().map(|file| file.id().as_str()).collect();
+        assert_eq!(
+            files,
+            ["abc123.png", "ghi789.mp4", "jkl012.zip", "new111.gif"]
+        );
+        assert_eq!(
+            forms,
+            [
+                "reqtype=editalbum&userhash=hash123&short=xyz789&title=Cats+%26+dogs&desc=\
+                 &files=abc123.png+def456.jpg+ghi789.mp4+jkl012.zip",
+                "reqtype=addtoalbum&short=xyz789&userhash=hash123&files=new111.gif",
+                "reqtype=removefromalbum&userhash=hash123&short=xyz789&files=def456.jpg",
+            ]
+        );
+
+        let (album, forms) = update(page, AlbumChanges::default()).await;
+        assert_eq!(album.unwrap().files.len(), 4);
+        assert!(forms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_incomplete_album() {
+        let page = include_str!("../../tests/fixtures/album.html").replace(
+            "https://files.catbox.moe/def456.jpg",
+            "https://mirror.example.com/def456.jpg",
+        );
+
+        let (res, forms) = update(
+            &page,
+            AlbumChanges {
+                title: Some("Dogs".to_string()),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        match res {
+            Err(CatboxError::IncompleteAlbum { links, .. }) => {
+                assert_eq!(links, ["https://mirror.example.com/def456.jpg"])
+            },
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(forms.is_empty());
+
+        let (res, forms) = update(
+            &page,
+            AlbumChanges {
+                add: ids(&["new111.gif"]),
+                ..AlbumChanges::default()
+            },
+        )
+        .await;
+        assert!(res.is_ok());
+        assert_eq!(
+            forms,
+            ["reqtype=addtoalbum&short=xyz789&userhash=hash123&files=new111.gif"]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_album() {
+        let url = serve(vec![ok(include_str!("../../tests/fixtures/album.html"))]).await;
+        let client = CatboxClient::builder().album_url(url.clone()).build();
+        let album = client.get_album("xyz789".parse().unwrap()).await.unwrap();
+        assert_eq!(album.url.as_str(), format!("{}xyz789", url));
+        assert_eq!(album.files.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn album_from_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cat.png");
+        std::fs::write(&file, "content").unwrap();
+        let url = serve(vec![
+            ok("https://files.catbox.moe/abc123.png"),
+            ok("https://catbox.moe/c/xyz789"),
+        ])
+        .await;
+        let client = CatboxClient::builder().catbox_api_url(url).build();
+
+        let missing = dir.path().join("missing.png").to_string_lossy().to_string();
+        let album = client
+            .create_album_from_files(
+                "Cats",
+                "",
+                vec![missing.clone(), file.to_string_lossy().to_string()],
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(album.url.short().as_str(), "xyz789");
+        assert_eq!(album.files.len(), 1);
+        assert_eq!(album.files[0].id().as_str(), "abc123.png");
+        assert_eq!(album.failed.len(), 1);
+        assert_eq!(album.failed[0].0, missing);
+
+        assert!(matches!(
+            client
+                .create_album_from_files("Cats", "", vec![missing], 1)
+                .await,
+            Err(CatboxError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn album_from_uploads() {
+        let (url, mut requests) = record(vec![ok("https://catbox.moe/c/xyz789")]).await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .user_hash("hash123")
+            .build();
+        // Later inputs finish first
+        let upload = |(delay, id): (u64, &'static str)| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+            match id {
+                "" => Err(CatboxError::Api("Not a file".to_string())),
+                id => Ok(FileUrl::new(FILES_URL, id.parse().unwrap())),
+            }
+        };
+        let album = client
+            .create_album_from_uploads(
+                "Cats",
+                "",
+                vec![(60, "abc123.png"), (40, ""), (20, "def456.jpg")],
+                3,
+                upload,
+            )
+            .await
+            .unwrap();
+        let files: Vec<_> = album.files.iter().map(|file| file.id().as_str()).collect();
+        assert_eq!(files, ["abc123.png", "def456.jpg"]);
+        assert_eq!(album.failed.len(), 1);
+        assert_eq!(album.failed[0].0, (40, ""));
+        assert_eq!(
+            requests.recv().await.unwrap().body,
+            b"reqtype=createalbum&userhash=hash123&title=Cats&desc=&files=abc123.png+def456.jpg"
+        );
+    }
 }
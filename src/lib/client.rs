@@ -0,0 +1,456 @@
+//! Reusable client for calling Catbox's and Litterbox's APIs
+//!
+//! A `CatboxClient` owns a single HTTP client, so connections are reused
+//! between requests, and an optional default user hash used for calls that
+//! need one.
+//!
+//! The API endpoints default to catbox.moe's, but can be changed to point the
+//! client at a self-hosted Catbox-compatible server or a local stand-in.
+//!
+//! The API calls themselves are implemented in the `file`, `album` and `litter` modules.
+
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::{
+    AlbumUrl, CATBOX_ALBUM_URL, CATBOX_API_URL, CATBOX_FILES_URL, CatboxError, ContentSniffing,
+    FileUrl, History, LITTER_API_URL, RetryPolicy, UASTRING, UploadLimits, response,
+};
+
+/// Client for Catbox's and Litterbox's APIs
+///
+/// Cloning the client is cheap and the clones share the same connection pool.
+#[derive(Debug, Clone)]
+pub struct CatboxClient {
+    pub(crate) http: Client,
+    pub(crate) user_hash: Option<String>,
+    pub(crate) catbox_api_url: String,
+    pub(crate) litter_api_url: String,
+    pub(crate) files_url: String,
+    pub(crate) album_url: String,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) upload_limits: UploadLimits,
+    pub(crate) content_sniffing: ContentSniffing,
+    pub(crate) history: Option<History>,
+}
+
+impl CatboxClient {
+    /// Create a client with default settings and no user hash
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Start building a client with custom settings
+    pub fn builder() -> CatboxClientBuilder {
+        CatboxClientBuilder::default()
+    }
+
+    /// User hash sent with requests made through this client
+    pub fn user_hash(&self) -> Option<&str> {
+        self.user_hash.as_deref()
+    }
+
+    /// Catbox API endpoint used by this client
+    pub fn catbox_api_url(&self) -> &str {
+        &self.catbox_api_url
+    }
+
+    /// Litterbox API endpoint used by this client
+    pub fn litter_api_url(&self) -> &str {
+        &self.litter_api_url
+    }
+
+    /// Base URL of uploaded files, e.g. `https://files.catbox.moe/`
+    pub fn files_url(&self) -> &str {
+        &self.files_url
+    }
+
+    /// Base URL of albums, e.g. `https://catbox.moe/c/`
+    pub fn album_url(&self) -> &str {
+        &self.album_url
+    }
+
+    /// Policy for retrying failed requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Limits files are checked against before uploading
+    pub fn upload_limits(&self) -> &UploadLimits {
+        &self.upload_limits
+    }
+
+    /// What is done with the type of uploaded files detected from their contents
+    pub fn content_sniffing(&self) -> ContentSniffing {
+        self.content_sniffing
+    }
+
+    /// History successful calls are recorded in, if any
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    pub(crate) fn from_user_hash<S: Into<String>>(user_hash: Option<S>) -> Self {
+        Self {
+            user_hash: user_hash.map(Into::into),
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn user_hash_or_default(&self) -> String {
+        self.user_hash.clone().unwrap_or_default()
+    }
+
+    /// Post a form to `url`, retrying according to the retry policy,
+    /// and check the response body with `check`
+    pub(crate) async fn post_form<T, F>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        check: F,
+    ) -> Result<T, CatboxError>
+    where
+        F: Fn(String) -> Result<T, CatboxError>,
+    {
+        let check = &check;
+        self.with_retries(|| self.post_form_once(url, form, check))
+            .await
+    }
+
+    /// Post a form to `url` without retrying, and check the response body with `check`.
+    /// Used for calls that would be made twice if a failed attempt reached the server.
+    pub(crate) async fn post_form_once<T, F>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+        check: F,
+    ) -> Result<T, CatboxError>
+    where
+        F: FnOnce(String) -> Result<T, CatboxError>,
+    {
+        let response = self.http.post(url).form(form).send().await?;
+        response::text(response).await.and_then(check)
+    }
+
+    /// Parse a reply containing the URL of a file under the client's `files_url`
+    pub(crate) fn expect_file_url(&self, body: String) -> Result<FileUrl, CatboxError> {
+        response::expect_url(body, |url| FileUrl::parse_with(url, &self.files_url))
+    }
+
+    /// Parse a reply containing the URL of an album under the client's `album_url`
+    pub(crate) fn expect_album_url(&self, body: String) -> Result<AlbumUrl, CatboxError> {
+        response::expect_url(body, |url| AlbumUrl::parse_with(url, &self.album_url))
+    }
+}
+
+impl Default for CatboxClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for `CatboxClient`
+#[derive(Debug, Default)]
+pub struct CatboxClientBuilder {
+    http: Option<Client>,
+    user_hash: Option<String>,
+    catbox_api_url: Option<String>,
+    litter_api_url: Option<String>,
+    files_url: Option<String>,
+    album_url: Option<String>,
+    retry_policy: RetryPolicy,
+    upload_limits: UploadLimits,
+    content_sniffing: ContentSniffing,
+    history: Option<History>,
+    timeout: Option<Duration>,
+}
+
+impl CatboxClientBuilder {
+    /// Use an already configured `reqwest::Client` for all requests
+    ///
+    /// If not given, a client with this crate's user agent is created.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - HTTP client to use
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http = Some(client);
+        self
+    }
+
+    /// Set a timeout for every request, from connecting until the response has been read
+    ///
+    /// Defaults to no timeout. Ignored if an HTTP client is given with `http_client`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Longest time a request may take
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the default user hash
+    ///
+    /// # Arguments
+    ///
+    /// * `user_hash` - User's account hash, required for deleting or editing.
+    pub fn user_hash<S: Into<String>>(mut self, user_hash: S) -> Self {
+        self.user_hash = Some(user_hash.into());
+        self
+    }
+
+    /// Set the Catbox API endpoint
+    ///
+    /// Defaults to `https://catbox.moe/user/api.php`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of a Catbox-compatible API
+    pub fn catbox_api_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.catbox_api_url = Some(url.into());
+        self
+    }
+
+    /// Set the Litterbox API endpoint
+    ///
+    /// Defaults to `https://litterbox.catbox.moe/resources/internals/api.php`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of a Litterbox-compatible API
+    pub fn litter_api_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.litter_api_url = Some(url.into());
+        self
+    }
+
+    /// Set the base URL uploaded files are served from
+    ///
+    /// Defaults to `https://files.catbox.moe/`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Base URL of uploaded files
+    pub fn files_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.files_url = Some(url.into());
+        self
+    }
+
+    /// Set the base URL albums are served from
+    ///
+    /// Defaults to `https://catbox.moe/c/`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Base URL of albums
+    pub fn album_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.album_url = Some(url.into());
+        self
+    }
+
+    /// Set the policy for retrying requests that failed for transient reasons
+    ///
+    /// Defaults to not retrying.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How many times and how often to retry
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Set the limits files are checked against before uploading
+    ///
+    /// Defaults to catbox.moe's limits. Use `UploadLimits::none()` for servers
+    /// with different limits or to let the server decide.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - Largest accepted sizes and refused extensions
+    pub fn upload_limits(mut self, limits: UploadLimits) -> Self {
+        self.upload_limits = limits;
+        self
+    }
+
+    /// Set whether to detect the type of uploaded files from their contents
+    ///
+    /// Defaults to `ContentSniffing::Off`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sniffing` - What to do with the detected type
+    pub fn content_sniffing(mut self, sniffing: ContentSniffing) -> Self {
+        self.content_sniffing = sniffing;
+        self
+    }
+
+    /// Record successful uploads, album creations and deletions in `history`
+    ///
+    /// Not recorded by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - History to append to, e.g. `History::new(path)`
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Create the client
+    pub fn build(self) -> CatboxClient {
+        CatboxClient {
+            http: self.http.unwrap_or_else(|| {
+                let builder = Client::builder().user_agent(UASTRING);
+                match self.timeout {
+                    Some(timeout) => builder.timeout(timeout),
+                    None => builder,
+                }
+                .build()
+                .unwrap_or_else(|_| Client::new())
+            }),
+            user_hash: self.user_hash,
+            catbox_api_url: self
+                .catbox_api_url
+                .unwrap_or_else(|| CATBOX_API_URL.to_string()),
+            litter_api_url: self
+                .litter_api_url
+                .unwrap_or_else(|| LITTER_API_URL.to_string()),
+            files_url: with_trailing_slash(self.files_url.as_deref().unwrap_or(CATBOX_FILES_URL)),
+            album_url: with_trailing_slash(self.album_url.as_deref().unwrap_or(CATBOX_ALBUM_URL)),
+            retry_policy: self.retry_policy,
+            upload_limits: self.upload_limits,
+            content_sniffing: self.content_sniffing,
+            history: self.history,
+        }
+    }
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    match url.ends_with('/') {
+        true => url.to_string(),
+        false => format!("{}/", url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ok, record, serve};
+
+    #[tokio::test]
+    async fn builder_defaults() {
+        let client = CatboxClient::new();
+        assert_eq!(client.user_hash(), None);
+        assert_eq!(client.catbox_api_url(), CATBOX_API_URL);
+        assert_eq!(client.litter_api_url(), LITTER_API_URL);
+        assert_eq!(client.files_url(), CATBOX_FILES_URL);
+        assert_eq!(client.album_url(), CATBOX_ALBUM_URL);
+        assert_eq!(client.retry_policy(), &RetryPolicy::default());
+        assert_eq!(client.content_sniffing(), ContentSniffing::default());
+        assert!(client.history().is_none());
+
+        let (url, mut requests) = record(vec![ok("Files successfully deleted.")]).await;
+        CatboxClient::builder()
+            .catbox_api_url(&url)
+            .build()
+            .delete_files(vec!["abc123.png".parse().unwrap()])
+            .await
+            .unwrap();
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.line(), "POST / HTTP/1.1");
+        assert_eq!(request.header("user-agent"), Some(UASTRING));
+        assert_eq!(
+            request.body,
+            b"reqtype=deletefiles&userhash=&files=abc123.png"
+        );
+    }
+
+    #[tokio::test]
+    async fn builder_overrides() {
+        let (url, mut requests) = record(vec![
+            ok("Files successfully deleted."),
+            ok("https://litter.catbox.moe/abc123.txt"),
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .http_client(Client::builder().user_agent("test").build().unwrap())
+            .user_hash("hash123")
+            .catbox_api_url(format!("{}catbox.php", url))
+            .litter_api_url(format!("{}litter.php", url))
+            .files_url("https://example.com/files")
+            .album_url("https://example.com/albums/")
+            .build();
+        assert_eq!(client.user_hash(), Some("hash123"));
+        assert_eq!(client.files_url(), "https://example.com/files/");
+        assert_eq!(client.album_url(), "https://example.com/albums/");
+
+        client
+            .delete_files(vec![
+                "abc123.png".parse().unwrap(),
+                "def456.jpg".parse().unwrap(),
+            ])
+            .await
+            .unwrap();
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.line(), "POST /catbox.php HTTP/1.1");
+        assert_eq!(request.header("user-agent"), Some("test"));
+        assert_eq!(
+            request.body,
+            b"reqtype=deletefiles&userhash=hash123&files=abc123.png+def456.jpg"
+        );
+
+        client
+            .upload_litter_bytes("contents", "file.txt", crate::LitterExpiry::OneHour)
+            .await
+            .unwrap();
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.line(), "POST /litter.php HTTP/1.1");
+        assert_eq!(
+            request.fields()[..2],
+            [
+                ("reqtype".to_string(), "fileupload".to_string()),
+                ("time".to_string(), "1h".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_under_custom_base_urls() {
+        let url = serve(vec![
+            ok("https://example.com/catbox/files/abc123.png"),
+            ok("https://example.com/catbox/files/def456.png"),
+            ok("https://example.com/catbox/c/xyz789"),
+            ok("https://example.com/catbox/c/xyz789"),
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .user_hash("hash123")
+            .catbox_api_url(&url)
+            .files_url("https://example.com/catbox/files/")
+            .album_url("https://example.com/catbox/c/")
+            .build();
+
+        let file = client.upload_bytes("contents", "file.png").await.unwrap();
+        assert_eq!(file.as_str(), "https://example.com/catbox/files/abc123.png");
+        assert_eq!(file.id().as_str(), "abc123.png");
+        let file = client
+            .upload_url("https://example.org/def456.png")
+            .await
+            .unwrap();
+        assert_eq!(file.id().as_str(), "def456.png");
+
+        let album = client
+            .create_album("Title", "", vec![file.id().clone()])
+            .await
+            .unwrap();
+        assert_eq!(album.as_str(), "https://example.com/catbox/c/xyz789");
+        assert_eq!(album.short().as_str(), "xyz789");
+        let album = client
+            .add_to_album(album.short().clone(), vec!["abc123.png".parse().unwrap()])
+            .await
+            .unwrap();
+        assert_eq!(album.short().as_str(), "xyz789");
+    }
+}
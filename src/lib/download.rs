@@ -0,0 +1,346 @@
+//! Functions for downloading files from Catbox and Litterbox
+//!
+//! Files can be streamed to any `AsyncWrite`, or saved to a path. Saving goes
+//! through a partial file next to the path, so an interrupted download can be
+//! resumed with an HTTP range request by saving to the same path again.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt;
+use reqwest::{
+    Response, StatusCode,
+    header::{CONTENT_RANGE, RANGE},
+};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncWrite, AsyncWriteExt},
+};
+
+use crate::{CatboxClient, CatboxError, FileUrl, Progress, response};
+
+/// Appended to the path of a file while it's being saved by `download_to_file`
+pub static PARTIAL_SUFFIX: &str = ".part";
+
+impl CatboxClient {
+    /// Download a file, writing its contents to `writer`
+    ///
+    /// Returns the number of bytes written
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the file on Catbox or Litterbox
+    /// * `writer` - Destination of the contents
+    pub async fn download<W: AsyncWrite + Unpin>(
+        &self,
+        url: &FileUrl,
+        writer: &mut W,
+    ) -> Result<u64, CatboxError> {
+        self.download_with_progress(url, writer, |_| {}).await
+    }
+
+    /// Download a file, writing its contents to `writer` and reporting the download's progress
+    ///
+    /// Returns the number of bytes written
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the file on Catbox or Litterbox
+    /// * `writer` - Destination of the contents
+    /// * `progress` - Called with the bytes received so far and the file's size
+    pub async fn download_with_progress<W, F>(
+        &self,
+        url: &FileUrl,
+        writer: &mut W,
+        progress: F,
+    ) -> Result<u64, CatboxError>
+    where
+        W: AsyncWrite + Unpin,
+        F: Fn(Progress),
+    {
+        let response = self.get_file(url, 0).await?;
+        let total = response.content_length();
+        write_body(response, writer, 0, total, &progress).await
+    }
+
+    /// Download a file to `path`, resuming an earlier download that was interrupted
+    ///
+    /// The contents are written to `path` with `PARTIAL_SUFFIX` appended, which is
+    /// renamed to `path` once complete. An existing file at `path` is replaced.
+    ///
+    /// Returns the size of the file
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the file on Catbox or Litterbox
+    /// * `path` - Where to save the file
+    pub async fn download_to_file(&self, url: &FileUrl, path: &Path) -> Result<u64, CatboxError> {
+        self.download_to_file_with_progress(url, path, |_| {}).await
+    }
+
+    /// Download a file to `path`, resuming an earlier download that was interrupted,
+    /// and report the download's progress
+    ///
+    /// The contents are written to `path` with `PARTIAL_SUFFIX` appended, which is
+    /// renamed to `path` once complete. An existing file at `path` is replaced.
+    ///
+    /// Returns the size of the file
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the file on Catbox or Litterbox
+    /// * `path` - Where to save the file
+    /// * `progress` - Called with the bytes received so far, including those of
+    ///   the earlier download, and the file's size
+    pub async fn download_to_file_with_progress<F>(
+        &self,
+        url: &FileUrl,
+        path: &Path,
+        progress: F,
+    ) -> Result<u64, CatboxError>
+    where
+        F: Fn(Progress),
+    {
+        let partial = partial_path(path);
+        let mut offset = match fs::metadata(&partial).await {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err.into()),
+        };
+        let response = loop {
+            let response = self.get_file(url, offset).await?;
+            if response.status() != StatusCode::RANGE_NOT_SATISFIABLE {
+                break response;
+            }
+            // Nothing is left to download, unless the partial file isn't of this file
+            if complete_length(&response) == Some(offset) {
+                fs::rename(&partial, path).await?;
+                progress(Progress {
+                    sent: offset,
+                    total: offset,
+                });
+                return Ok(offset);
+            }
+            offset = 0;
+        };
+
+        // The server sends the whole file if it doesn't support ranges
+        let start = match response.status() {
+            StatusCode::PARTIAL_CONTENT => offset,
+            _ => 0,
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(start > 0)
+            .truncate(start == 0)
+            .open(&partial)
+            .await?;
+        let total = response.content_length().map(|length| start + length);
+        let written = write_body(response, &mut file, start, total, &progress).await;
+        // Whatever was received is kept for resuming
+        file.flush().await?;
+        let size = written?;
+        drop(file);
+        fs::rename(&partial, path).await?;
+        Ok(size)
+    }
+
+    /// Request a file, from byte `offset` on if it's not 0
+    ///
+    /// A `416 Range Not Satisfiable` response is returned rather than an error,
+    /// since it's expected when the earlier download was already complete.
+    async fn get_file(&self, url: &FileUrl, offset: u64) -> Result<Response, CatboxError> {
+        self.with_retries(|| async {
+            let mut request = self.http.get(url.as_str());
+            if offset > 0 {
+                request = request.header(RANGE, format!("bytes={}-", offset));
+            }
+            let response = request.send().await?;
+            match offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                true => Ok(response),
+                false => response::check(response).await,
+            }
+        })
+        .await
+    }
+}
+
+/// Download a file, writing its contents to `writer`
+///
+/// Returns the number of bytes written
+///
+/// # Arguments
+///
+/// * `url` - URL of the file on Catbox or Litterbox
+/// * `writer` - Destination of the contents
+pub async fn download<W: AsyncWrite + Unpin>(
+    url: &FileUrl,
+    writer: &mut W,
+) -> Result<u64, CatboxError> {
+    CatboxClient::new().download(url, writer).await
+}
+
+/// Download a file to `path`, resuming an earlier download that was interrupted
+///
+/// Returns the size of the file
+///
+/// # Arguments
+///
+/// * `url` - URL of the file on Catbox or Litterbox
+/// * `path` - Where to save the file
+pub async fn to_file(url: &FileUrl, path: &Path) -> Result<u64, CatboxError> {
+    CatboxClient::new().download_to_file(url, path).await
+}
+
+/// Path of the partial file `download_to_file` writes to before renaming it to `path`
+///
+/// # Arguments
+///
+/// * `path` - Where the file is saved
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    PathBuf::from(partial)
+}
+
+/// Size of the whole file from the `Content-Range` of a `416` response, e.g. `bytes */1234`
+fn complete_length(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes */")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Write the body of `response` to `writer`
+///
+/// Returns `start` plus the number of bytes written, or an error if the
+/// body ended before its `Content-Length`
+async fn write_body<W, F>(
+    response: Response,
+    writer: &mut W,
+    start: u64,
+    total: Option<u64>,
+    progress: &F,
+) -> Result<u64, CatboxError>
+where
+    W: AsyncWrite + Unpin,
+    F: Fn(Progress),
+{
+    let expected = response.content_length();
+    let mut received = 0;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        writer.write_all(&chunk).await?;
+        received += chunk.len() as u64;
+        progress(Progress {
+            sent: start + received,
+            total: total.unwrap_or(start + received),
+        });
+    }
+    writer.flush().await?;
+    match expected {
+        Some(expected) if received < expected => Err(CatboxError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "Download ended after {} of {} bytes",
+                start + received,
+                start + expected
+            ),
+        ))),
+        _ => Ok(start + received),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ok, serve};
+
+    #[tokio::test]
+    async fn download_to_writer() {
+        let url = serve(vec![ok("file contents")]).await;
+        let mut contents = Vec::new();
+        let size = CatboxClient::new()
+            .download(
+                &format!("{}abc123.txt", url).parse().unwrap(),
+                &mut contents,
+            )
+            .await
+            .unwrap();
+        assert_eq!(size, 13);
+        assert_eq!(contents, b"file contents");
+    }
+
+    #[tokio::test]
+    async fn resume_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc123.txt");
+        std::fs::write(partial_path(&path), "file ").unwrap();
+        let url = serve(vec![
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-12/13\r\n\
+             Content-Length: 8\r\nConnection: close\r\n\r\ncontents",
+            "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */13\r\n\
+             Content-Length: 0\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+        let client = CatboxClient::new();
+        let file = format!("{}abc123.txt", url).parse().unwrap();
+
+        assert_eq!(client.download_to_file(&file, &path).await.unwrap(), 13);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "file contents");
+        assert!(!partial_path(&path).exists());
+
+        // A complete partial file is only renamed
+        std::fs::rename(&path, partial_path(&path)).unwrap();
+        assert_eq!(client.download_to_file(&file, &path).await.unwrap(), 13);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "file contents");
+    }
+
+    #[tokio::test]
+    async fn restart_without_range_support() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc123.txt");
+        std::fs::write(partial_path(&path), "stale").unwrap();
+        let url = serve(vec![ok("file contents")]).await;
+        let file = format!("{}abc123.txt", url).parse().unwrap();
+
+        let size = CatboxClient::new()
+            .download_to_file(&file, &path)
+            .await
+            .unwrap();
+        assert_eq!(size, 13);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "file contents");
+    }
+
+    #[tokio::test]
+    async fn truncated_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc123.txt");
+        let url = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\nConnection: close\r\n\r\nfile",
+        ])
+        .await;
+        let file = format!("{}abc123.txt", url).parse().unwrap();
+
+        assert!(
+            CatboxClient::new()
+                .download_to_file(&file, &path)
+                .await
+                .is_err()
+        );
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read_to_string(partial_path(&path)).unwrap(),
+            "file"
+        );
+    }
+}
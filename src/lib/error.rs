@@ -0,0 +1,122 @@
+//! Error type returned by the API calls
+
+use std::{error::Error, fmt, io, time::Duration};
+
+use reqwest::StatusCode;
+
+/// Reasons an API call can fail
+#[derive(Debug)]
+pub enum CatboxError {
+    /// Opening or reading a local file failed
+    Io(io::Error),
+    /// Sending the request or receiving the response failed
+    Http(reqwest::Error),
+    /// The server responded with a non-success HTTP status
+    Status {
+        /// HTTP status of the response
+        status: StatusCode,
+        /// Body of the response
+        body: String,
+        /// How long the server asked to wait before retrying, from the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+    /// Catbox replied with an error message instead of a result,
+    /// e.g. "No album found for user specified."
+    Api(String),
+    /// The file is larger than the service accepts. Checked before uploading.
+    TooLarge {
+        /// Size of the file in bytes
+        size: u64,
+        /// Largest accepted size in bytes
+        limit: u64,
+    },
+    /// The service doesn't accept files with this extension. Checked before uploading.
+    BlockedExtension(String),
+    /// Some files of an album couldn't be read from its page, so it wasn't
+    /// edited to avoid dropping them
+    IncompleteAlbum {
+        /// URL of the album
+        url: String,
+        /// Links on the page that look like files but weren't recognized as ones
+        links: Vec<String>,
+    },
+}
+
+impl CatboxError {
+    /// Whether the request might succeed if retried: failed connections, timeouts
+    /// before any response, 5xx statuses and 429 Too Many Requests
+    pub fn is_transient(&self) -> bool {
+        match self {
+            CatboxError::Http(err) => {
+                err.is_connect() || (err.is_timeout() && !err.is_body() && !err.is_decode())
+            },
+            CatboxError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            },
+            CatboxError::Io(_)
+            | CatboxError::Api(_)
+            | CatboxError::TooLarge { .. }
+            | CatboxError::BlockedExtension(_)
+            | CatboxError::IncompleteAlbum { .. } => false,
+        }
+    }
+
+    /// How long the server asked to wait before retrying, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CatboxError::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CatboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatboxError::Io(err) => write!(f, "I/O error: {}", err),
+            CatboxError::Http(err) => write!(f, "HTTP error: {}", err),
+            CatboxError::Status { status, .. } => write!(f, "Server responded with {}", status),
+            CatboxError::Api(message) => write!(f, "Catbox error: {}", message),
+            CatboxError::TooLarge { size, limit } => write!(
+                f,
+                "File is too large ({} bytes, the limit is {} bytes)",
+                size, limit
+            ),
+            CatboxError::BlockedExtension(extension) => {
+                write!(f, "Files with the .{} extension are not allowed", extension)
+            },
+            CatboxError::IncompleteAlbum { url, links } => write!(
+                f,
+                "Not all files of {} could be read from its page ({}), editing it could drop them",
+                url,
+                links.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for CatboxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CatboxError::Io(err) => Some(err),
+            CatboxError::Http(err) => Some(err),
+            CatboxError::Status { .. }
+            | CatboxError::Api(_)
+            | CatboxError::TooLarge { .. }
+            | CatboxError::BlockedExtension(_)
+            | CatboxError::IncompleteAlbum { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for CatboxError {
+    fn from(err: io::Error) -> Self {
+        CatboxError::Io(err)
+    }
+}
+
+impl From<reqwest::Error> for CatboxError {
+    fn from(err: reqwest::Error) -> Self {
+        CatboxError::Http(err)
+    }
+}
@@ -4,14 +4,288 @@
 //!
 //! See <https://catbox.moe/faq.php> for allowed filetypes and content.
 
-use std::error::Error;
+use std::{error::Error, future::Future, sync::Arc};
 
-use reqwest::{
-    multipart::{Form, Part},
-    Client,
+use bytes::Bytes;
+use futures::{TryStream, TryStreamExt};
+use reqwest::multipart::{Form, Part};
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    CatboxClient, CatboxError, FileId, FileUrl, Progress,
+    helper::*,
+    history::{Action, ContentHash, Entry, Source},
+    limits::StreamLimit,
+    response,
+    sniff::named_part,
 };
 
-use crate::{helper::*, CATBOX_API_URL, UASTRING};
+impl CatboxClient {
+    /// Upload a file to catbox.
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file to be uploaded
+    pub async fn upload_file<S: Into<String>>(&self, file_path: S) -> Result<FileUrl, CatboxError> {
+        let file_path = &file_path.into();
+        let (file_name, mime) = &self.sniff_file(file_path).await?;
+        self.check_catbox_file(file_path, file_name).await?;
+        let hash = &ContentHash::default();
+        let url = self
+            .upload_part(|| async move {
+                let file = file_stream(file_path, hash).await?;
+                named_part(Part::stream(file), file_name, *mime)
+            })
+            .await?;
+        self.record_upload(
+            Action::Upload,
+            Source::File(file_path, hash),
+            Some(file_name),
+            &url,
+            None,
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload a file to catbox, reporting the upload's progress.
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file to be uploaded
+    /// * `progress` - Called with the bytes sent so far and the file's size
+    pub async fn upload_file_with_progress<S, F>(
+        &self,
+        file_path: S,
+        progress: F,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        S: Into<String>,
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        let file_path = &file_path.into();
+        let (file_name, mime) = &self.sniff_file(file_path).await?;
+        self.check_catbox_file(file_path, file_name).await?;
+        let hash = &ContentHash::default();
+        let progress = Arc::new(progress);
+        let url = self
+            .upload_part(|| {
+                let progress = progress.clone();
+                async move {
+                    let (file, total) =
+                        file_stream_with_progress(file_path, hash, move |sent| progress(sent))
+                            .await?;
+                    named_part(Part::stream_with_length(file, total), file_name, *mime)
+                }
+            })
+            .await?;
+        self.record_upload(
+            Action::Upload,
+            Source::File(file_path, hash),
+            Some(file_name),
+            &url,
+            None,
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload contents from memory to catbox.
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Contents of the file, e.g. a `Vec<u8>` or `Bytes`
+    /// * `file_name` - Name of the file, including the extension
+    pub async fn upload_bytes<B: Into<Bytes>, S: Into<String>>(
+        &self,
+        bytes: B,
+        file_name: S,
+    ) -> Result<FileUrl, CatboxError> {
+        let bytes = &bytes.into();
+        let (file_name, mime) = &self.sniff_bytes(bytes, file_name.into());
+        self.upload_limits
+            .check_catbox(file_name, Some(bytes.len() as u64))?;
+        let url = self
+            .upload_part(|| async move {
+                let length = bytes.len() as u64;
+                named_part(
+                    Part::stream_with_length(bytes.clone(), length),
+                    file_name,
+                    *mime,
+                )
+            })
+            .await?;
+        self.record_upload(
+            Action::Upload,
+            Source::Bytes(bytes),
+            Some(file_name),
+            &url,
+            None,
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload contents of a reader to catbox.
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// Not retried on failure, since the reader can only be read once.
+    /// Stops with `CatboxError::TooLarge` once more than the size limit has been read.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the contents
+    /// * `file_name` - Name of the file, including the extension
+    pub async fn upload_reader<R, S>(&self, reader: R, file_name: S) -> Result<FileUrl, CatboxError>
+    where
+        R: AsyncRead + Send + Sync + 'static,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        self.upload_limits.check_catbox(&file_name, None)?;
+        let limit = StreamLimit::new(self.upload_limits.catbox_max_size);
+        let url = self
+            .send_upload(
+                Part::stream(limit.body(ReaderStream::new(reader))).file_name(file_name.clone()),
+            )
+            .await
+            .map_err(|err| limit.error(err))?;
+        self.record_upload(Action::Upload, Source::Stream, Some(&file_name), &url, None)
+            .await;
+        Ok(url)
+    }
+
+    /// Upload contents of a stream of bytes to catbox.
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// Not retried on failure, since the stream can only be read once.
+    /// Stops with `CatboxError::TooLarge` once more than the size limit has been read.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream of chunks of the contents
+    /// * `file_name` - Name of the file, including the extension
+    pub async fn upload_stream<T, S>(&self, stream: T, file_name: S) -> Result<FileUrl, CatboxError>
+    where
+        T: TryStream + Send + Sync + 'static,
+        T::Error: Into<Box<dyn Error + Send + Sync>>,
+        Bytes: From<T::Ok>,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        self.upload_limits.check_catbox(&file_name, None)?;
+        let limit = StreamLimit::new(self.upload_limits.catbox_max_size);
+        let url = self
+            .send_upload(
+                Part::stream(limit.body(stream.map_ok(Bytes::from))).file_name(file_name.clone()),
+            )
+            .await
+            .map_err(|err| limit.error(err))?;
+        self.record_upload(Action::Upload, Source::Stream, Some(&file_name), &url, None)
+            .await;
+        Ok(url)
+    }
+
+    /// Upload the part created by `part`, creating it again for every attempt
+    async fn upload_part<F, Fut>(&self, part: F) -> Result<FileUrl, CatboxError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Part, CatboxError>>,
+    {
+        let part = &part;
+        self.with_retries(|| async move { self.send_upload(part().await?).await })
+            .await
+    }
+
+    async fn send_upload(&self, part: Part) -> Result<FileUrl, CatboxError> {
+        let form = Form::new()
+            .text("reqtype", "fileupload")
+            .text("userhash", self.user_hash_or_default())
+            .part("fileToUpload", part);
+
+        let response = self
+            .http
+            .post(&self.catbox_api_url)
+            .multipart(form)
+            .send()
+            .await?;
+        response::text(response)
+            .await
+            .and_then(|body| self.expect_file_url(body))
+    }
+
+    /// Upload contents from an URL to catbox
+    ///
+    /// Returns an URL to the file
+    ///
+    /// See <https://catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL to file
+    pub async fn upload_url<S: Into<String>>(&self, url: S) -> Result<FileUrl, CatboxError> {
+        let url = url.into();
+        let form = [
+            ("reqtype", "urlupload"),
+            ("userhash", &self.user_hash_or_default()),
+            ("url", &url),
+        ];
+        let res = self
+            .post_form(&self.catbox_api_url, &form, |body| {
+                self.expect_file_url(body)
+            })
+            .await?;
+        self.record_upload(Action::UrlUpload, Source::Url(&url), None, &res, None)
+            .await;
+        Ok(res)
+    }
+
+    /// Delete files
+    ///
+    /// Requires the client to have a user hash.
+    ///
+    /// Returns "Files successfully deleted." on success
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Names of the files to be deleted
+    pub async fn delete_files(&self, files: Vec<FileId>) -> Result<String, CatboxError> {
+        let files: Vec<_> = files.iter().map(FileId::as_str).collect();
+        let form = [
+            ("reqtype", "deletefiles"),
+            ("userhash", &self.user_hash_or_default()),
+            ("files", &files.join(" ")),
+        ];
+        let res = self
+            .post_form(&self.catbox_api_url, &form, |body| {
+                response::expect_message(body, "successfully deleted")
+            })
+            .await?;
+        let mut entry = Entry::new(Action::Delete);
+        entry.files = files.iter().map(|file| file.to_string()).collect();
+        self.record(entry).await;
+        Ok(res)
+    }
+}
 
 /// Upload a file to catbox.
 ///
@@ -26,31 +300,108 @@ use crate::{helper::*, CATBOX_API_URL, UASTRING};
 pub async fn from_file<S: Into<String>>(
     file_path: S,
     user_hash: Option<S>,
-) -> Result<String, Box<dyn Error>> {
-    let file_path = file_path.into();
-    let file = file_stream(&file_path).await?;
-    let file_name = file_name(&file_path);
-
-    let form = Form::new()
-        .text("reqtype", "fileupload")
-        .text(
-            "userhash",
-            user_hash
-                .and_then(|hash| Some(hash.into()))
-                .unwrap_or_default(),
-        )
-        .part("fileToUpload", Part::stream(file).file_name(file_name));
-
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .multipart(form)
-        .send()
-        .await?
-        .text()
-        .await?)
+) -> Result<FileUrl, CatboxError> {
+    CatboxClient::from_user_hash(user_hash)
+        .upload_file(file_path)
+        .await
+}
+
+/// Upload a file to catbox, reporting the upload's progress.
+///
+/// Returns an URL to the file
+///
+/// See <https://catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file to be uploaded
+/// * `user_hash` - User's account hash, required for deleting. (Optional)
+/// * `progress` - Called with the bytes sent so far and the file's size
+pub async fn from_file_with_progress<S, F>(
+    file_path: S,
+    user_hash: Option<S>,
+    progress: F,
+) -> Result<FileUrl, CatboxError>
+where
+    S: Into<String>,
+    F: Fn(Progress) + Send + Sync + 'static,
+{
+    CatboxClient::from_user_hash(user_hash)
+        .upload_file_with_progress(file_path, progress)
+        .await
+}
+
+/// Upload contents from memory to catbox.
+///
+/// Returns an URL to the file
+///
+/// See <https://catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `bytes` - Contents of the file, e.g. a `Vec<u8>` or `Bytes`
+/// * `file_name` - Name of the file, including the extension
+/// * `user_hash` - User's account hash, required for deleting. (Optional)
+pub async fn from_bytes<B: Into<Bytes>, S: Into<String>>(
+    bytes: B,
+    file_name: S,
+    user_hash: Option<S>,
+) -> Result<FileUrl, CatboxError> {
+    CatboxClient::from_user_hash(user_hash)
+        .upload_bytes(bytes, file_name)
+        .await
+}
+
+/// Upload contents of a reader to catbox.
+///
+/// Returns an URL to the file
+///
+/// See <https://catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the contents
+/// * `file_name` - Name of the file, including the extension
+/// * `user_hash` - User's account hash, required for deleting. (Optional)
+pub async fn from_reader<R, S>(
+    reader: R,
+    file_name: S,
+    user_hash: Option<S>,
+) -> Result<FileUrl, CatboxError>
+where
+    R: AsyncRead + Send + Sync + 'static,
+    S: Into<String>,
+{
+    CatboxClient::from_user_hash(user_hash)
+        .upload_reader(reader, file_name)
+        .await
+}
+
+/// Upload contents of a stream of bytes to catbox.
+///
+/// Returns an URL to the file
+///
+/// See <https://catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `stream` - Stream of chunks of the contents
+/// * `file_name` - Name of the file, including the extension
+/// * `user_hash` - User's account hash, required for deleting. (Optional)
+pub async fn from_stream<T, S>(
+    stream: T,
+    file_name: S,
+    user_hash: Option<S>,
+) -> Result<FileUrl, CatboxError>
+where
+    T: TryStream + Send + Sync + 'static,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+    Bytes: From<T::Ok>,
+    S: Into<String>,
+{
+    CatboxClient::from_user_hash(user_hash)
+        .upload_stream(stream, file_name)
+        .await
 }
 
 /// Upload contents from an URL to catbox
@@ -66,27 +417,10 @@ pub async fn from_file<S: Into<String>>(
 pub async fn from_url<S: Into<String>>(
     url: S,
     user_hash: Option<S>,
-) -> Result<String, Box<dyn Error>> {
-    let form = [
-        ("reqtype", "urlupload"),
-        (
-            "userhash",
-            &user_hash
-                .and_then(|hash| Some(hash.into()))
-                .unwrap_or_default(),
-        ),
-        ("url", &url.into()),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+) -> Result<FileUrl, CatboxError> {
+    CatboxClient::from_user_hash(user_hash)
+        .upload_url(url)
+        .await
 }
 
 /// Delete files
@@ -99,22 +433,150 @@ pub async fn from_url<S: Into<String>>(
 /// * `files` - Names of the files to be deleted
 pub async fn delete<S: Into<String>>(
     user_hash: S,
-    files: Vec<S>,
-) -> Result<String, Box<dyn Error>> {
-    let files: Vec<_> = files.into_iter().map(|file| file.into()).collect();
-    let form = [
-        ("reqtype", "deletefiles"),
-        ("userhash", &user_hash.into()),
-        ("files", &files.join(" ")),
-    ];
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(CATBOX_API_URL)
-        .form(&form)
-        .send()
-        .await?
-        .text()
-        .await?)
+    files: Vec<FileId>,
+) -> Result<String, CatboxError> {
+    CatboxClient::from_user_hash(Some(user_hash))
+        .delete_files(files)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use futures::stream;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{
+        UploadLimits,
+        testing::{Request, ok, record, serve},
+    };
+
+    /// Check that `request` uploads `contents` as `file_name` to Catbox
+    fn assert_upload(request: &Request, file_name: &str, contents: &str) {
+        let parts = request.parts();
+        assert_eq!(
+            request.fields()[..2],
+            [
+                ("reqtype".to_string(), "fileupload".to_string()),
+                ("userhash".to_string(), "hash123".to_string())
+            ]
+        );
+        assert_eq!(parts[2].name, "fileToUpload");
+        assert_eq!(parts[2].file_name.as_deref(), Some(file_name));
+        assert_eq!(parts[2].value, contents);
+    }
+
+    #[tokio::test]
+    async fn upload_contents() {
+        let (url, mut requests) = record(vec![
+            ok("https://files.catbox.moe/abc123.txt"),
+            ok("https://files.catbox.moe/def456.txt"),
+            ok("https://files.catbox.moe/ghi789.txt"),
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(&url)
+            .user_hash("hash123")
+            .build();
+
+        let file = client
+            .upload_bytes("from bytes", "bytes.txt")
+            .await
+            .unwrap();
+        assert_eq!(file.as_str(), "https://files.catbox.moe/abc123.txt");
+        let request = requests.recv().await.unwrap();
+        assert_upload(&request, "bytes.txt", "from bytes");
+        assert_eq!(request.header("transfer-encoding"), None);
+
+        let reader = "from a reader and more".as_bytes().take(13);
+        let file = client.upload_reader(reader, "reader.txt").await.unwrap();
+        assert_eq!(file.id().as_str(), "def456.txt");
+        let request = requests.recv().await.unwrap();
+        assert_upload(&request, "reader.txt", "from a reader");
+        assert_eq!(request.header("transfer-encoding"), Some("chunked"));
+
+        let chunks: Vec<Result<_, io::Error>> = vec![Ok("from "), Ok("a "), Ok("stream")];
+        let file = client
+            .upload_stream(stream::iter(chunks), "stream.txt")
+            .await
+            .unwrap();
+        assert_eq!(file.id().as_str(), "ghi789.txt");
+        assert_upload(
+            &requests.recv().await.unwrap(),
+            "stream.txt",
+            "from a stream",
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_progress() {
+        let mut file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        std::io::Write::write_all(&mut file, &[b'x'; 10000]).unwrap();
+        let (url, mut requests) = record(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: \
+             0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+            ok("https://files.catbox.moe/abc123.txt"),
+        ])
+        .await;
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = CatboxClient::builder()
+            .catbox_api_url(&url)
+            .retry_policy(crate::RetryPolicy::new(2))
+            .build();
+        let progress = reported.clone();
+        client
+            .upload_file_with_progress(file.path().to_str().unwrap(), move |sent| {
+                progress.lock().unwrap().push(sent)
+            })
+            .await
+            .unwrap();
+        assert_eq!(requests.recv().await.unwrap().parts()[2].value.len(), 10000);
+        assert_eq!(requests.recv().await.unwrap().parts()[2].value.len(), 10000);
+
+        // Progress starts over for the retried attempt
+        let reported = reported.lock().unwrap();
+        let attempts: Vec<_> = reported
+            .split_inclusive(|progress| progress.sent == progress.total)
+            .collect();
+        assert_eq!(attempts.len(), 2);
+        for attempt in attempts {
+            assert!(attempt.len() > 1);
+            assert!(attempt.iter().all(|progress| progress.total == 10000));
+            assert!(attempt.windows(2).all(|pair| pair[0].sent < pair[1].sent));
+            assert_eq!(attempt.last().unwrap().sent, 10000);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_names_are_not_sent() {
+        let (url, mut requests) = record(vec![ok("https://files.catbox.moe/abc123.exe")]).await;
+        let client = CatboxClient::builder().catbox_api_url(&url).build();
+        assert!(client.upload_bytes("contents", "file.exe").await.is_err());
+        assert!(
+            client
+                .upload_reader(&b"contents"[..], "file.exe")
+                .await
+                .is_err()
+        );
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn streams_stop_at_the_limit() {
+        let url = serve(vec![ok("https://files.catbox.moe/abc123.txt")]).await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(&url)
+            .upload_limits(UploadLimits {
+                catbox_max_size: Some(4),
+                ..UploadLimits::default()
+            })
+            .build();
+        assert!(matches!(
+            client.upload_reader(&b"too long"[..], "file.txt").await,
+            Err(CatboxError::TooLarge { size: 8, limit: 4 })
+        ));
+    }
 }
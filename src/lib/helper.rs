@@ -1,19 +1,52 @@
 //! Helper functions for handling files
-use std::{error::Error, path::Path};
+use std::{io, path::Path};
 
+use futures::TryStreamExt;
 use reqwest::Body;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-/// Return a Body wrapping a stream to the file's contents
+use crate::{Progress, history::ContentHash};
+
+/// Return a Body wrapping a stream to the file's contents.
+/// `hash` is started over and fed the contents as they're read.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file
+/// * `hash` - Hash of the contents sent, for the history
+pub async fn file_stream(file_path: &str, hash: &ContentHash) -> Result<Body, io::Error> {
+    Ok(file_stream_with_progress(file_path, hash, |_| {}).await?.0)
+}
+
+/// Return a Body wrapping a stream to the file's contents and the file's size.
+/// `hash` is started over and fed the contents as they're read, and `progress`
+/// is called after each chunk read from the file.
 ///
 /// # Arguments
 ///
 /// * `file_path` - Path to the file
-pub async fn file_stream(file_path: &str) -> Result<Body, Box<dyn Error>> {
-    Ok(Body::wrap_stream(ReaderStream::new(
-        File::open(file_path).await?,
-    )))
+/// * `hash` - Hash of the contents sent, for the history
+/// * `progress` - Callback receiving the upload's progress
+pub async fn file_stream_with_progress<F>(
+    file_path: &str,
+    hash: &ContentHash,
+    progress: F,
+) -> Result<(Body, u64), io::Error>
+where
+    F: Fn(Progress) + Send + Sync + 'static,
+{
+    let file = File::open(file_path).await?;
+    let total = file.metadata().await?.len();
+    let mut sent = 0;
+    hash.reset();
+    let hash = hash.clone();
+    let stream = ReaderStream::new(file).inspect_ok(move |chunk| {
+        hash.update(chunk);
+        sent += chunk.len() as u64;
+        progress(Progress { sent, total });
+    });
+    Ok((Body::wrap_stream(stream), total))
 }
 
 /// Strip off the directory and return the file's name and extension
@@ -0,0 +1,638 @@
+//! Local record of uploads, albums and deletions
+//!
+//! A `CatboxClient` given a `History` appends an `Entry` for every successful
+//! upload, URL upload, Litterbox upload, album creation and deletion made
+//! through it. Entries are stored as JSON lines, by default in
+//! `$XDG_DATA_HOME/catbox/history.jsonl`.
+//!
+//! Failing to write the history doesn't fail the call that was recorded.
+//! Recorded entries can be narrowed down with a `Filter`.
+
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::{AlbumShort, CatboxClient, FileUrl, LitterExpiry};
+
+/// What was done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// File uploaded to Catbox
+    Upload,
+    /// File uploaded to Catbox from an URL
+    UrlUpload,
+    /// Temporary file uploaded to Litterbox
+    LitterUpload,
+    /// Files deleted from Catbox
+    Delete,
+    /// Album created
+    AlbumCreate,
+    /// Album deleted
+    AlbumDelete,
+}
+
+/// Service a call was made to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    /// catbox.moe or a compatible server
+    Catbox,
+    /// litterbox.catbox.moe or a compatible server
+    Litterbox,
+}
+
+/// Error returned when a string is not a known service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseServiceError(String);
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Service::Catbox => f.pad("catbox"),
+            Service::Litterbox => f.pad("litterbox"),
+        }
+    }
+}
+
+impl FromStr for Service {
+    type Err = ParseServiceError;
+
+    /// Accepts `catbox` and `litterbox`, or `litter` for short
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "catbox" => Ok(Service::Catbox),
+            "litterbox" | "litter" => Ok(Service::Litterbox),
+            _ => Err(ParseServiceError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ParseServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not a valid service (Options: catbox, litterbox)",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseServiceError {}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Action::Upload => "upload",
+            Action::UrlUpload => "url_upload",
+            Action::LitterUpload => "litter_upload",
+            Action::Delete => "delete",
+            Action::AlbumCreate => "album_create",
+            Action::AlbumDelete => "album_delete",
+        })
+    }
+}
+
+/// One recorded call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the call was made
+    pub time: DateTime<Utc>,
+    /// What was done
+    pub action: Action,
+    /// Path of the uploaded file or URL it was uploaded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Name the file was uploaded as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Size of the uploaded file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// SHA-256 of the uploaded file's contents, in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// URL of the uploaded file or created album
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// When a Litterbox upload expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    /// User hash the call was made with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_hash: Option<String>,
+    /// Title of the created album
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Short of the created or deleted album
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Files deleted or put in the created album
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+}
+
+impl Entry {
+    /// Entry for `action` made now
+    pub fn new(action: Action) -> Self {
+        Self {
+            time: Utc::now(),
+            action,
+            source: None,
+            name: None,
+            size: None,
+            sha256: None,
+            url: None,
+            expires: None,
+            user_hash: None,
+            title: None,
+            album: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Service the call was made to
+    pub fn service(&self) -> Service {
+        match self.action {
+            Action::LitterUpload => Service::Litterbox,
+            _ => Service::Catbox,
+        }
+    }
+}
+
+/// Conditions an entry has to meet. Conditions that are `None` match every entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Made at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Made before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Made to this service
+    pub service: Option<Service>,
+    /// Original name of the file contains this, ignoring case
+    pub name: Option<String>,
+    /// Concerns this album, i.e. is its creation or deletion. With `Filter::select`,
+    /// uploads of the files the album was created with match too.
+    pub album: Option<AlbumShort>,
+    /// Made with this user hash
+    pub user_hash: Option<String>,
+    /// Any of the source, name, URL, album title or files contains this, ignoring case
+    pub text: Option<String>,
+}
+
+impl Filter {
+    /// Whether `entry` meets all the conditions
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Entry to check
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.matches_with(entry, &[])
+    }
+
+    /// Entries of `entries` that meet all the conditions, with their indices
+    ///
+    /// Unlike `matches`, the album condition is also met by uploads of the files
+    /// the album was created with, as recorded by its creation in `entries`.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Entries to check, e.g. the whole history
+    pub fn select<'a>(&self, entries: &'a [Entry]) -> Vec<(usize, &'a Entry)> {
+        let album_files: Vec<&str> = match &self.album {
+            Some(album) => entries
+                .iter()
+                .filter(|entry| {
+                    entry.action == Action::AlbumCreate
+                        && entry.album.as_deref() == Some(album.as_str())
+                })
+                .flat_map(|entry| entry.files.iter().map(String::as_str))
+                .collect(),
+            None => Vec::new(),
+        };
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.matches_with(entry, &album_files))
+            .collect()
+    }
+
+    /// Whether `entry` meets all the conditions, counting uploads of `album_files`
+    /// as concerning the album
+    fn matches_with(&self, entry: &Entry, album_files: &[&str]) -> bool {
+        let contains = |field: Option<&str>, part: &str| {
+            field.is_some_and(|field| field.to_lowercase().contains(&part.to_lowercase()))
+        };
+        let original_name = entry
+            .source
+            .as_deref()
+            .and_then(|source| source.rsplit(['/', '\\']).next());
+        self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time < until)
+            && self
+                .service
+                .is_none_or(|service| entry.service() == service)
+            && self.name.as_deref().is_none_or(|name| {
+                contains(original_name, name) || contains(entry.name.as_deref(), name)
+            })
+            && self.album.as_ref().is_none_or(|album| {
+                entry.album.as_deref() == Some(album.as_str())
+                    || matches!(entry.action, Action::Upload | Action::UrlUpload)
+                        && entry
+                            .url
+                            .as_deref()
+                            .and_then(|url| url.rsplit('/').next())
+                            .is_some_and(|id| album_files.contains(&id))
+            })
+            && self
+                .user_hash
+                .as_deref()
+                .is_none_or(|user_hash| entry.user_hash.as_deref() == Some(user_hash))
+            && self.text.as_deref().is_none_or(|text| {
+                [
+                    entry.source.as_deref(),
+                    entry.name.as_deref(),
+                    entry.url.as_deref(),
+                    entry.title.as_deref(),
+                    entry.album.as_deref(),
+                ]
+                .into_iter()
+                .any(|field| contains(field, text))
+                    || entry.files.iter().any(|file| contains(Some(file), text))
+            })
+    }
+}
+
+/// Where an uploaded file came from
+pub(crate) enum Source<'a> {
+    /// File at a path, hashed while it was sent
+    File(&'a str, &'a ContentHash),
+    /// Contents in memory
+    Bytes(&'a [u8]),
+    /// Reader or stream, which can't be read again
+    Stream,
+    /// URL the server fetched the file from
+    Url(&'a str),
+}
+
+/// Size and SHA-256 of the contents sent by an upload, computed while they're
+/// streamed so that the file isn't read again for the history
+#[derive(Clone, Default)]
+pub(crate) struct ContentHash(Arc<Mutex<(u64, Sha256)>>);
+
+impl ContentHash {
+    /// Start over, for another attempt of the upload
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.0.lock() {
+            *state = Default::default();
+        }
+    }
+
+    /// Add a chunk of the contents
+    pub fn update(&self, chunk: &[u8]) {
+        if let Ok(mut state) = self.0.lock() {
+            state.0 += chunk.len() as u64;
+            state.1.update(chunk);
+        }
+    }
+
+    /// Size and hex-encoded SHA-256 of the contents since the last reset
+    pub fn finish(&self) -> Option<(u64, String)> {
+        let state = self.0.lock().ok()?;
+        Some((state.0, hex(&state.1.clone().finalize())))
+    }
+}
+
+/// History stored in a JSON lines file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// History stored in the file at `path`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file, created on the first write
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// `$XDG_DATA_HOME/catbox/history.jsonl` or the platform's equivalent
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("catbox").join("history.jsonl"))
+    }
+
+    /// Path to the file the history is stored in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry to the history
+    ///
+    /// The file and its directory are created if needed. On Unix the file
+    /// is only readable by its owner, since entries contain user hashes.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Entry to append
+    pub async fn record(&self, entry: &Entry) -> Result<(), io::Error> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&self.path).await?;
+        file.write_all(&line).await?;
+        file.flush().await
+    }
+
+    /// All entries in the history, oldest first
+    ///
+    /// Returns an empty list if nothing has been recorded yet.
+    /// Lines that can't be parsed are skipped.
+    pub fn entries(&self) -> Result<Vec<Entry>, io::Error> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+impl CatboxClient {
+    /// Record `entry` with the client's user hash, if the client has a history
+    pub(crate) async fn record(&self, mut entry: Entry) {
+        if let Some(history) = &self.history {
+            entry.user_hash = self.user_hash.clone();
+            let _ = history.record(&entry).await;
+        }
+    }
+
+    /// Record an uploaded file, if the client has a history
+    pub(crate) async fn record_upload(
+        &self,
+        action: Action,
+        source: Source<'_>,
+        name: Option<&str>,
+        url: &FileUrl,
+        expiry: Option<LitterExpiry>,
+    ) {
+        if self.history.is_none() {
+            return;
+        }
+        let mut entry = Entry::new(action);
+        match source {
+            Source::File(file_path, hash) => {
+                entry.source = Some(
+                    tokio::fs::canonicalize(file_path)
+                        .await
+                        .map(|path| path.to_string_lossy().to_string())
+                        .unwrap_or_else(|_| file_path.to_string()),
+                );
+                if let Some((size, hash)) = hash.finish() {
+                    entry.size = Some(size);
+                    entry.sha256 = Some(hash);
+                }
+            },
+            Source::Bytes(bytes) => {
+                entry.size = Some(bytes.len() as u64);
+                entry.sha256 = Some(hex(&Sha256::digest(bytes)));
+            },
+            Source::Stream => {},
+            Source::Url(source) => entry.source = Some(source.to_string()),
+        }
+        entry.name = name.map(str::to_string);
+        entry.url = Some(url.to_string());
+        entry.expires = expiry.and_then(|expiry| {
+            chrono::Duration::from_std(expiry.as_duration())
+                .ok()
+                .map(|duration| entry.time + duration)
+        });
+        self.record(entry).await;
+    }
+}
+
+/// Size and hex-encoded SHA-256 of the file's contents, as recorded in the history
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file
+pub async fn hash_file(file_path: &str) -> Result<(u64, String), io::Error> {
+    let mut file = File::open(file_path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        match file.read(&mut buf).await? {
+            0 => break,
+            read => {
+                hasher.update(&buf[..read]);
+                size += read as u64;
+            },
+        }
+    }
+    Ok((size, hex(&hasher.finalize())))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(dir.path().join("nested").join("history.jsonl"));
+        assert!(history.entries().unwrap().is_empty());
+
+        let mut entry = Entry::new(Action::Upload);
+        entry.url = Some("https://files.catbox.moe/abc123.png".to_string());
+        history.record(&entry).await.unwrap();
+        history
+            .record(&Entry::new(Action::AlbumDelete))
+            .await
+            .unwrap();
+
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry);
+        assert_eq!(entries[1].action, Action::AlbumDelete);
+    }
+
+    #[test]
+    fn filters() {
+        let mut upload = Entry::new(Action::Upload);
+        upload.source = Some("/home/user/Pictures/Cat.png".to_string());
+        upload.user_hash = Some("hash".to_string());
+        let mut album = Entry::new(Action::AlbumCreate);
+        album.album = Some("xyz789".to_string());
+        album.title = Some("Holiday".to_string());
+        let litter = Entry::new(Action::LitterUpload);
+
+        let matching = |filter: Filter| {
+            [&upload, &album, &litter]
+                .into_iter()
+                .filter(|entry| filter.matches(entry))
+                .count()
+        };
+        assert_eq!(matching(Filter::default()), 3);
+        assert_eq!(
+            matching(Filter {
+                service: Some(Service::Litterbox),
+                ..Filter::default()
+            }),
+            1
+        );
+        assert_eq!(
+            matching(Filter {
+                name: Some("cat".to_string()),
+                user_hash: Some("hash".to_string()),
+                ..Filter::default()
+            }),
+            1
+        );
+        assert_eq!(
+            matching(Filter {
+                name: Some("pictures".to_string()),
+                ..Filter::default()
+            }),
+            0
+        );
+        assert_eq!(
+            matching(Filter {
+                album: Some("https://catbox.moe/c/xyz789".parse().unwrap()),
+                ..Filter::default()
+            }),
+            1
+        );
+        assert_eq!(
+            matching(Filter {
+                text: Some("holiday".to_string()),
+                ..Filter::default()
+            }),
+            1
+        );
+        assert_eq!(
+            matching(Filter {
+                since: Some(Utc::now() + chrono::Duration::hours(1)),
+                ..Filter::default()
+            }),
+            0
+        );
+    }
+
+    #[test]
+    fn selects_album_uploads() {
+        let mut kept = Entry::new(Action::Upload);
+        kept.url = Some("https://files.catbox.moe/abc123.png".to_string());
+        let mut other = Entry::new(Action::Upload);
+        other.url = Some("https://files.catbox.moe/def456.jpg".to_string());
+        let mut album = Entry::new(Action::AlbumCreate);
+        album.album = Some("xyz789".to_string());
+        album.files = vec!["abc123.png".to_string()];
+        let entries = [kept.clone(), other, album.clone()];
+
+        let filter = Filter {
+            album: Some("xyz789".parse().unwrap()),
+            ..Filter::default()
+        };
+        assert_eq!(filter.select(&entries), [(0, &kept), (2, &album)]);
+        assert!(!filter.matches(&kept));
+    }
+
+    #[tokio::test]
+    async fn records_uploads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let client = CatboxClient::builder()
+            .user_hash("hash")
+            .history(History::new(&path))
+            .build();
+        let url: FileUrl = "abc123.txt".parse().unwrap();
+        client
+            .record_upload(
+                Action::LitterUpload,
+                Source::Bytes(b"content"),
+                Some("file.txt"),
+                &url,
+                Some(LitterExpiry::OneDay),
+            )
+            .await;
+
+        let entry = &History::new(&path).entries().unwrap()[0];
+        assert_eq!(entry.size, Some(7));
+        assert_eq!(
+            entry.sha256.as_deref(),
+            Some("ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73")
+        );
+        assert_eq!(entry.user_hash.as_deref(), Some("hash"));
+        assert_eq!(
+            entry.expires,
+            Some(entry.time + chrono::Duration::hours(24))
+        );
+    }
+
+    #[tokio::test]
+    async fn records_uploaded_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "content").unwrap();
+        let url = crate::testing::serve(vec![
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: \
+             close\r\n\r\n"
+                .to_string(),
+            crate::testing::ok("https://files.catbox.moe/abc123.txt"),
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .retry_policy(crate::RetryPolicy {
+                initial_backoff: std::time::Duration::ZERO,
+                ..crate::RetryPolicy::new(2)
+            })
+            .history(History::new(&path))
+            .build();
+        client.upload_file(file.to_str().unwrap()).await.unwrap();
+
+        // The contents of the retried attempt are hashed once
+        let entry = &History::new(&path).entries().unwrap()[0];
+        assert_eq!(
+            entry.source.as_deref(),
+            Some(file.canonicalize().unwrap().to_str().unwrap())
+        );
+        assert_eq!(entry.size, Some(7));
+        assert_eq!(
+            entry.sha256,
+            Some(hash_file(file.to_str().unwrap()).await.unwrap().1)
+        );
+    }
+}
@@ -1,9 +1,26 @@
 //! Unofficial library implementing catbox.moe's API in Rust
 //!
-//! Separates funtionalities into three modules:
+//! Separates funtionalities into four modules:
 //! * `file` for uploading and deleting singular files
-//! * `album` for album operations with existing files on Catbox
+//! * `album` for album operations with existing files on Catbox, creating
+//!   an album from local files, or reading an album's contents as an `Album`
 //! * `litter` for uploading temporary files to Litterbox
+//! * `download` for downloading files from either service, to any `AsyncWrite`
+//!   or to a path where interrupted downloads are resumed
+//!
+//! Each module has free functions for one-off calls. For repeated calls,
+//! create a `CatboxClient` and use its methods, which share one HTTP client.
+//!
+//! IDs and URLs of files and albums are represented by the types in `types`,
+//! which can be parsed from both bare IDs and full URLs.
+//!
+//! All calls return a `CatboxError` on failure, including when the server
+//! responds with an error status or replies with anything but the expected
+//! result, e.g. an error message instead of an URL. Uploads
+//! are checked against the services' size and filetype limits in `limits`
+//! before anything is sent.
+//!
+//! A client can keep a local `History` of its uploads, albums and deletions.
 //!
 //! See <https://catbox.moe/faq.php> for allowed filetypes and content,
 //! as well as other questions.
@@ -11,13 +28,43 @@
 //! Consider donating via <https://www.patreon.com/catbox> to help with server costs.
 
 mod helper;
+mod page;
+mod response;
+#[cfg(test)]
+mod testing;
 
 pub mod album;
+pub mod client;
+pub mod download;
+pub mod error;
 pub mod file;
+pub mod history;
+pub mod limits;
 pub mod litter;
+pub mod progress;
+pub mod retry;
+pub mod sniff;
+pub mod types;
+
+pub use album::{Album, AlbumChanges, AlbumUpload};
+pub use client::{CatboxClient, CatboxClientBuilder};
+pub use error::CatboxError;
+pub use history::History;
+pub use limits::UploadLimits;
+pub use litter::LitterExpiry;
+pub use progress::Progress;
+pub use retry::RetryPolicy;
+pub use sniff::{ContentSniffing, ContentType};
+pub use types::{AlbumShort, AlbumUrl, FileId, FileUrl, ParseIdError};
 
-static CATBOX_API_URL: &str = "https://catbox.moe/user/api.php";
-static LITTER_API_URL: &str = "https://litterbox.catbox.moe/resources/internals/api.php";
+/// Default Catbox API endpoint
+pub static CATBOX_API_URL: &str = "https://catbox.moe/user/api.php";
+/// Default Litterbox API endpoint
+pub static LITTER_API_URL: &str = "https://litterbox.catbox.moe/resources/internals/api.php";
+/// Default base URL of files uploaded to Catbox
+pub static CATBOX_FILES_URL: &str = "https://files.catbox.moe/";
+/// Default base URL of Catbox albums
+pub static CATBOX_ALBUM_URL: &str = "https://catbox.moe/c/";
 static UASTRING: &str = concat!(
     env!("CARGO_PKG_NAME"),
     "/",
@@ -0,0 +1,235 @@
+//! Checking files against the services' limits before uploading them
+//!
+//! Catbox rejects files over 200MB and Litterbox files over 1GB, and both
+//! refuse some filetypes, but only after the whole file has been sent.
+//! A `CatboxClient` checks the size and extension of a file first and fails
+//! with `CatboxError::TooLarge` or `CatboxError::BlockedExtension` without
+//! making a request.
+//!
+//! Readers and streams have no size up front, so their uploads are stopped
+//! with `CatboxError::TooLarge` once more than the limit has been sent.
+//!
+//! Self-hosted servers may have other limits. Set custom `UploadLimits`,
+//! or `UploadLimits::none()`, on the client to change or skip the checks.
+
+use std::{
+    error::Error,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use bytes::Bytes;
+use futures::{StreamExt, TryStream, TryStreamExt};
+use reqwest::Body;
+
+use crate::{CatboxClient, CatboxError};
+
+/// Largest file accepted by Catbox, 200MB
+pub const CATBOX_MAX_SIZE: u64 = 200 * 1024 * 1024;
+/// Largest file accepted by Litterbox, 1GB
+pub const LITTER_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+/// Extensions refused by Catbox and Litterbox. A trailing `*` matches any ending.
+pub const BLOCKED_EXTENSIONS: &[&str] = &["exe", "scr", "cpl", "doc*", "jar"];
+
+/// Limits files are checked against before uploading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadLimits {
+    /// Largest file size in bytes accepted by Catbox, unlimited if `None`
+    pub catbox_max_size: Option<u64>,
+    /// Largest file size in bytes accepted by Litterbox, unlimited if `None`
+    pub litter_max_size: Option<u64>,
+    /// Refused extensions without the leading dot, compared case-insensitively.
+    /// A trailing `*` matches any ending, e.g. `doc*` matches `docx`.
+    pub blocked_extensions: Vec<String>,
+}
+
+impl UploadLimits {
+    /// Don't check anything
+    pub fn none() -> Self {
+        Self {
+            catbox_max_size: None,
+            litter_max_size: None,
+            blocked_extensions: Vec::new(),
+        }
+    }
+
+    /// Check a file to be uploaded to Catbox
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file, including the extension
+    /// * `size` - Size of the file in bytes, if known
+    pub fn check_catbox(&self, file_name: &str, size: Option<u64>) -> Result<(), CatboxError> {
+        self.check(file_name, size, self.catbox_max_size)
+    }
+
+    /// Check a file to be uploaded to Litterbox
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file, including the extension
+    /// * `size` - Size of the file in bytes, if known
+    pub fn check_litter(&self, file_name: &str, size: Option<u64>) -> Result<(), CatboxError> {
+        self.check(file_name, size, self.litter_max_size)
+    }
+
+    fn check(
+        &self,
+        file_name: &str,
+        size: Option<u64>,
+        max_size: Option<u64>,
+    ) -> Result<(), CatboxError> {
+        if let Some(extension) = Path::new(file_name).extension() {
+            let extension = extension.to_string_lossy().to_lowercase();
+            if self.is_blocked(&extension) {
+                return Err(CatboxError::BlockedExtension(extension));
+            }
+        }
+        match (size, max_size) {
+            (Some(size), Some(limit)) if size > limit => Err(CatboxError::TooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_blocked(&self, extension: &str) -> bool {
+        self.blocked_extensions.iter().any(|blocked| {
+            let blocked = blocked.to_lowercase();
+            match blocked.strip_suffix('*') {
+                Some(prefix) => extension.starts_with(prefix),
+                None => extension == blocked,
+            }
+        })
+    }
+}
+
+impl Default for UploadLimits {
+    /// Limits of catbox.moe and litterbox.catbox.moe
+    fn default() -> Self {
+        Self {
+            catbox_max_size: Some(CATBOX_MAX_SIZE),
+            litter_max_size: Some(LITTER_MAX_SIZE),
+            blocked_extensions: BLOCKED_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl CatboxClient {
+    /// Check the file at `file_path`, uploaded as `file_name`, against the limits of Catbox
+    pub(crate) async fn check_catbox_file(
+        &self,
+        file_path: &str,
+        file_name: &str,
+    ) -> Result<(), CatboxError> {
+        let size = tokio::fs::metadata(file_path).await?.len();
+        self.upload_limits.check_catbox(file_name, Some(size))
+    }
+
+    /// Check the file at `file_path`, uploaded as `file_name`, against the limits of Litterbox
+    pub(crate) async fn check_litter_file(
+        &self,
+        file_path: &str,
+        file_name: &str,
+    ) -> Result<(), CatboxError> {
+        let size = tokio::fs::metadata(file_path).await?.len();
+        self.upload_limits.check_litter(file_name, Some(size))
+    }
+}
+
+/// Size limit of an upload whose size isn't known until it has been read
+pub(crate) struct StreamLimit {
+    sent: Arc<AtomicU64>,
+    max_size: Option<u64>,
+}
+
+impl StreamLimit {
+    /// # Arguments
+    ///
+    /// * `max_size` - Largest accepted size in bytes, unlimited if `None`
+    pub fn new(max_size: Option<u64>) -> Self {
+        Self {
+            sent: Arc::new(AtomicU64::new(0)),
+            max_size,
+        }
+    }
+
+    /// Body sending `stream`, failing as soon as it grows past the limit
+    pub fn body<T>(&self, stream: T) -> Body
+    where
+        T: TryStream<Ok = Bytes> + Send + Sync + 'static,
+        T::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let sent = self.sent.clone();
+        let max_size = self.max_size;
+        let stream = stream.into_stream().map(move |chunk| {
+            let chunk = chunk.map_err(Into::into)?;
+            let size = sent.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+            match max_size {
+                Some(limit) if size > limit => Err(CatboxError::TooLarge { size, limit }.into()),
+                _ => Ok::<_, Box<dyn Error + Send + Sync>>(chunk),
+            }
+        });
+        Body::wrap_stream(stream)
+    }
+
+    /// `CatboxError::TooLarge` if the upload failed from growing past the limit, otherwise `err`
+    pub fn error(&self, err: CatboxError) -> CatboxError {
+        let size = self.sent.load(Ordering::Relaxed);
+        match self.max_size {
+            Some(limit) if size > limit => CatboxError::TooLarge { size, limit },
+            _ => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocked_extensions() {
+        let limits = UploadLimits::default();
+        for name in [
+            "setup.exe",
+            "SETUP.EXE",
+            "notes.doc",
+            "notes.docx",
+            "app.jar",
+        ] {
+            assert!(matches!(
+                limits.check_catbox(name, Some(1)),
+                Err(CatboxError::BlockedExtension(_))
+            ));
+        }
+        for name in ["image.png", "exe", "archive.zip", "document.pdf"] {
+            assert!(limits.check_catbox(name, Some(1)).is_ok());
+        }
+        assert!(UploadLimits::none().check_catbox("setup.exe", None).is_ok());
+    }
+
+    #[test]
+    fn file_sizes() {
+        let limits = UploadLimits::default();
+        assert!(limits.check_catbox("a.png", Some(CATBOX_MAX_SIZE)).is_ok());
+        assert!(matches!(
+            limits.check_catbox("a.png", Some(CATBOX_MAX_SIZE + 1)),
+            Err(CatboxError::TooLarge { .. })
+        ));
+        assert!(
+            limits
+                .check_litter("a.png", Some(CATBOX_MAX_SIZE + 1))
+                .is_ok()
+        );
+        assert!(
+            limits
+                .check_litter("a.png", Some(LITTER_MAX_SIZE + 1))
+                .is_err()
+        );
+        assert!(limits.check_catbox("a.png", None).is_ok());
+    }
+}
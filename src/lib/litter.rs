@@ -4,14 +4,376 @@
 //!
 //! See <https://litterbox.catbox.moe/faq.php> for allowed filetypes and content.
 
-use std::error::Error;
+use std::{error::Error, fmt, future::Future, str::FromStr, sync::Arc, time::Duration};
 
-use reqwest::{
-    multipart::{Form, Part},
-    Client,
+use bytes::Bytes;
+use futures::{TryStream, TryStreamExt};
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    CatboxClient, CatboxError, FileUrl, Progress,
+    helper::*,
+    history::{Action, ContentHash, Source},
+    limits::StreamLimit,
+    response,
+    sniff::named_part,
 };
 
-use crate::{helper::*, LITTER_API_URL, UASTRING};
+/// How long Litterbox keeps an uploaded file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LitterExpiry {
+    /// 1 hour
+    #[default]
+    OneHour,
+    /// 12 hours
+    TwelveHours,
+    /// 24 hours
+    OneDay,
+    /// 72 hours
+    ThreeDays,
+}
+
+/// Error returned when a string is not a valid Litterbox expiry time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseExpiryError(String);
+
+impl LitterExpiry {
+    /// Time until expiration
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_secs(self.hours() * 60 * 60)
+    }
+
+    fn hours(&self) -> u64 {
+        match self {
+            LitterExpiry::OneHour => 1,
+            LitterExpiry::TwelveHours => 12,
+            LitterExpiry::OneDay => 24,
+            LitterExpiry::ThreeDays => 72,
+        }
+    }
+}
+
+impl fmt::Display for LitterExpiry {
+    /// Formats the value as Litterbox's API expects it, e.g. `24h`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}h", self.hours())
+    }
+}
+
+impl FromStr for LitterExpiry {
+    type Err = ParseExpiryError;
+
+    /// Accepts hours with or without a unit, like `12` or `12h`, and days like `1d` or `3d`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim().to_lowercase();
+        let (number, unit) = input.split_at(input.trim_end_matches(['h', 'd']).len());
+        let err = || ParseExpiryError(s.to_string());
+        let number = number.parse::<u64>().map_err(|_| err())?;
+        let hours = match unit {
+            "" | "h" => Some(number),
+            "d" => number.checked_mul(24),
+            _ => None,
+        };
+        match hours.ok_or_else(err)? {
+            1 => Ok(LitterExpiry::OneHour),
+            12 => Ok(LitterExpiry::TwelveHours),
+            24 => Ok(LitterExpiry::OneDay),
+            72 => Ok(LitterExpiry::ThreeDays),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl fmt::Display for ParseExpiryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not a valid expiry time (Options: 1h, 12h, 24h/1d, 72h/3d)",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseExpiryError {}
+
+impl Serialize for LitterExpiry {
+    /// Serialized as the API value, e.g. `24h`
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LitterExpiry {
+    /// Deserialized from any string accepted by `from_str`
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl CatboxClient {
+    /// Upload a temporary file to litterbox.
+    /// Max size 1GB.
+    ///
+    /// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file to be uploaded
+    /// * `time` - Time until expiration
+    pub async fn upload_litter<S: Into<String>>(
+        &self,
+        file_path: S,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError> {
+        let file_path = &file_path.into();
+        let (file_name, mime) = &self.sniff_file(file_path).await?;
+        self.check_litter_file(file_path, file_name).await?;
+        let hash = &ContentHash::default();
+        let url = self
+            .upload_litter_part(
+                || async move {
+                    let file = file_stream(file_path, hash).await?;
+                    named_part(Part::stream(file), file_name, *mime)
+                },
+                time,
+            )
+            .await?;
+        self.record_upload(
+            Action::LitterUpload,
+            Source::File(file_path, hash),
+            Some(file_name),
+            &url,
+            Some(time),
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload a temporary file to litterbox, reporting the upload's progress.
+    /// Max size 1GB.
+    ///
+    /// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file to be uploaded
+    /// * `time` - Time until expiration
+    /// * `progress` - Called with the bytes sent so far and the file's size
+    pub async fn upload_litter_with_progress<S, F>(
+        &self,
+        file_path: S,
+        time: LitterExpiry,
+        progress: F,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        S: Into<String>,
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        let file_path = &file_path.into();
+        let (file_name, mime) = &self.sniff_file(file_path).await?;
+        self.check_litter_file(file_path, file_name).await?;
+        let hash = &ContentHash::default();
+        let progress = Arc::new(progress);
+        let url = self
+            .upload_litter_part(
+                || {
+                    let progress = progress.clone();
+                    async move {
+                        let (file, total) =
+                            file_stream_with_progress(file_path, hash, move |sent| progress(sent))
+                                .await?;
+                        named_part(Part::stream_with_length(file, total), file_name, *mime)
+                    }
+                },
+                time,
+            )
+            .await?;
+        self.record_upload(
+            Action::LitterUpload,
+            Source::File(file_path, hash),
+            Some(file_name),
+            &url,
+            Some(time),
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload contents from memory to litterbox as a temporary file.
+    /// Max size 1GB.
+    ///
+    /// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Contents of the file, e.g. a `Vec<u8>` or `Bytes`
+    /// * `file_name` - Name of the file, including the extension
+    /// * `time` - Time until expiration
+    pub async fn upload_litter_bytes<B: Into<Bytes>, S: Into<String>>(
+        &self,
+        bytes: B,
+        file_name: S,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError> {
+        let bytes = &bytes.into();
+        let (file_name, mime) = &self.sniff_bytes(bytes, file_name.into());
+        self.upload_limits
+            .check_litter(file_name, Some(bytes.len() as u64))?;
+        let url = self
+            .upload_litter_part(
+                || async move {
+                    let length = bytes.len() as u64;
+                    named_part(
+                        Part::stream_with_length(bytes.clone(), length),
+                        file_name,
+                        *mime,
+                    )
+                },
+                time,
+            )
+            .await?;
+        self.record_upload(
+            Action::LitterUpload,
+            Source::Bytes(bytes),
+            Some(file_name),
+            &url,
+            Some(time),
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload contents of a reader to litterbox as a temporary file.
+    /// Max size 1GB.
+    ///
+    /// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// Not retried on failure, since the reader can only be read once.
+    /// Stops with `CatboxError::TooLarge` once more than the size limit has been read.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the contents
+    /// * `file_name` - Name of the file, including the extension
+    /// * `time` - Time until expiration
+    pub async fn upload_litter_reader<R, S>(
+        &self,
+        reader: R,
+        file_name: S,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        R: AsyncRead + Send + Sync + 'static,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        self.upload_limits.check_litter(&file_name, None)?;
+        let limit = StreamLimit::new(self.upload_limits.litter_max_size);
+        let url = self
+            .send_litter_upload(
+                Part::stream(limit.body(ReaderStream::new(reader))).file_name(file_name.clone()),
+                time,
+            )
+            .await
+            .map_err(|err| limit.error(err))?;
+        self.record_upload(
+            Action::LitterUpload,
+            Source::Stream,
+            Some(&file_name),
+            &url,
+            Some(time),
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload contents of a stream of bytes to litterbox as a temporary file.
+    /// Max size 1GB.
+    ///
+    /// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+    ///
+    /// Not retried on failure, since the stream can only be read once.
+    /// Stops with `CatboxError::TooLarge` once more than the size limit has been read.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream of chunks of the contents
+    /// * `file_name` - Name of the file, including the extension
+    /// * `time` - Time until expiration
+    pub async fn upload_litter_stream<T, S>(
+        &self,
+        stream: T,
+        file_name: S,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        T: TryStream + Send + Sync + 'static,
+        T::Error: Into<Box<dyn Error + Send + Sync>>,
+        Bytes: From<T::Ok>,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        self.upload_limits.check_litter(&file_name, None)?;
+        let limit = StreamLimit::new(self.upload_limits.litter_max_size);
+        let url = self
+            .send_litter_upload(
+                Part::stream(limit.body(stream.map_ok(Bytes::from))).file_name(file_name.clone()),
+                time,
+            )
+            .await
+            .map_err(|err| limit.error(err))?;
+        self.record_upload(
+            Action::LitterUpload,
+            Source::Stream,
+            Some(&file_name),
+            &url,
+            Some(time),
+        )
+        .await;
+        Ok(url)
+    }
+
+    /// Upload the part created by `part`, creating it again for every attempt
+    async fn upload_litter_part<F, Fut>(
+        &self,
+        part: F,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Part, CatboxError>>,
+    {
+        let part = &part;
+        self.with_retries(|| async move { self.send_litter_upload(part().await?, time).await })
+            .await
+    }
+
+    async fn send_litter_upload(
+        &self,
+        part: Part,
+        time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError> {
+        let form = Form::new()
+            .text("reqtype", "fileupload")
+            .text("time", time.to_string())
+            .part("fileToUpload", part);
+
+        let response = self
+            .http
+            .post(&self.litter_api_url)
+            .multipart(form)
+            .send()
+            .await?;
+        response::text(response)
+            .await
+            .and_then(|body| self.expect_file_url(body))
+    }
+}
 
 /// Upload a temporary file to litterbox.
 /// Max size 1GB.
@@ -21,25 +383,130 @@ use crate::{helper::*, LITTER_API_URL, UASTRING};
 /// # Arguments
 ///
 /// * `file_path` - Path to the file to be uploaded
-/// * `time` - Time until expiration. Valid values are 1, 12, 24 and 72.
-pub async fn upload<S: Into<String>>(file_path: S, time: u8) -> Result<String, Box<dyn Error>> {
-    let file_path = file_path.into();
-    let file = file_stream(&file_path).await?;
-    let file_name = file_name(&file_path);
-
-    let form = Form::new()
-        .text("reqtype", "fileupload")
-        .text("time", format!("{}h", time))
-        .part("fileToUpload", Part::stream(file).file_name(file_name));
-
-    Ok(Client::builder()
-        .user_agent(UASTRING)
-        .build()
-        .unwrap_or_else(|_| Client::new())
-        .post(LITTER_API_URL)
-        .multipart(form)
-        .send()
-        .await?
-        .text()
-        .await?)
+/// * `time` - Time until expiration
+pub async fn upload<S: Into<String>>(
+    file_path: S,
+    time: LitterExpiry,
+) -> Result<FileUrl, CatboxError> {
+    CatboxClient::new().upload_litter(file_path, time).await
+}
+
+/// Upload a temporary file to litterbox, reporting the upload's progress.
+/// Max size 1GB.
+///
+/// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file to be uploaded
+/// * `time` - Time until expiration
+/// * `progress` - Called with the bytes sent so far and the file's size
+pub async fn upload_with_progress<S, F>(
+    file_path: S,
+    time: LitterExpiry,
+    progress: F,
+) -> Result<FileUrl, CatboxError>
+where
+    S: Into<String>,
+    F: Fn(Progress) + Send + Sync + 'static,
+{
+    CatboxClient::new()
+        .upload_litter_with_progress(file_path, time, progress)
+        .await
+}
+
+/// Upload contents from memory to litterbox as a temporary file.
+/// Max size 1GB.
+///
+/// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `bytes` - Contents of the file, e.g. a `Vec<u8>` or `Bytes`
+/// * `file_name` - Name of the file, including the extension
+/// * `time` - Time until expiration
+pub async fn upload_bytes<B: Into<Bytes>, S: Into<String>>(
+    bytes: B,
+    file_name: S,
+    time: LitterExpiry,
+) -> Result<FileUrl, CatboxError> {
+    CatboxClient::new()
+        .upload_litter_bytes(bytes, file_name, time)
+        .await
+}
+
+/// Upload contents of a reader to litterbox as a temporary file.
+/// Max size 1GB.
+///
+/// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the contents
+/// * `file_name` - Name of the file, including the extension
+/// * `time` - Time until expiration
+pub async fn upload_reader<R, S>(
+    reader: R,
+    file_name: S,
+    time: LitterExpiry,
+) -> Result<FileUrl, CatboxError>
+where
+    R: AsyncRead + Send + Sync + 'static,
+    S: Into<String>,
+{
+    CatboxClient::new()
+        .upload_litter_reader(reader, file_name, time)
+        .await
+}
+
+/// Upload contents of a stream of bytes to litterbox as a temporary file.
+/// Max size 1GB.
+///
+/// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `stream` - Stream of chunks of the contents
+/// * `file_name` - Name of the file, including the extension
+/// * `time` - Time until expiration
+pub async fn upload_stream<T, S>(
+    stream: T,
+    file_name: S,
+    time: LitterExpiry,
+) -> Result<FileUrl, CatboxError>
+where
+    T: TryStream + Send + Sync + 'static,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+    Bytes: From<T::Ok>,
+    S: Into<String>,
+{
+    CatboxClient::new()
+        .upload_litter_stream(stream, file_name, time)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expiry() {
+        for (input, expiry) in [
+            ("1", LitterExpiry::OneHour),
+            ("1h", LitterExpiry::OneHour),
+            ("12H", LitterExpiry::TwelveHours),
+            ("24h", LitterExpiry::OneDay),
+            ("1d", LitterExpiry::OneDay),
+            ("72", LitterExpiry::ThreeDays),
+            ("3d", LitterExpiry::ThreeDays),
+        ] {
+            assert_eq!(input.parse(), Ok(expiry));
+        }
+        for input in ["5h", "2d", "h", "", "1hd", "-1"] {
+            assert!(input.parse::<LitterExpiry>().is_err());
+        }
+        // Days that overflow in hours, and would wrap around to 72h
+        assert!("2305843009213693955d".parse::<LitterExpiry>().is_err());
+        assert_eq!(LitterExpiry::ThreeDays.to_string(), "72h");
+    }
 }
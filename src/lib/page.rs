@@ -0,0 +1,177 @@
+//! Helper functions for reading values out of Catbox's HTML pages
+//!
+//! The pages are simple enough that scanning their tags is sufficient,
+//! without parsing the whole document.
+
+/// Iterate over the opening tags named `name`, e.g. `meta`, yielding their attributes
+///
+/// # Arguments
+///
+/// * `html` - Contents of the page
+/// * `name` - Tag name, in lowercase
+pub fn tags<'a>(html: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    tag_spans(html, name)
+        .into_iter()
+        .map(move |(start, end)| &html[start..end])
+}
+
+/// Start and end of the attributes of each opening tag named `name`
+fn tag_spans(html: &str, name: &str) -> Vec<(usize, usize)> {
+    // Lowercasing ASCII keeps the byte offsets the same
+    let lowercase = html.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(found) = lowercase[from..].find(&open) {
+        let start = from + found + open.len();
+        from = start;
+        // `<a` must not match `<abbr` or `<area`
+        match html[start..].chars().next() {
+            Some(c) if c.is_ascii_whitespace() || c == '>' || c == '/' => {
+                let end = html[start..]
+                    .find('>')
+                    .map_or(html.len(), |end| start + end);
+                spans.push((start, end));
+            },
+            _ => continue,
+        }
+    }
+    spans
+}
+
+/// Value of the attribute `name` in the attributes of a tag, with entities decoded
+///
+/// # Arguments
+///
+/// * `attributes` - Attributes of a tag, as returned by `tags`
+/// * `name` - Attribute name, in lowercase
+pub fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let inner = &after[1..];
+                        let end = inner.find(quote).unwrap_or(inner.len());
+                        (&inner[..end], inner.get(end + 1..).unwrap_or_default())
+                    },
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_ascii_whitespace())
+                            .unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    },
+                };
+                rest = remaining;
+                value
+            },
+            None => "",
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(unescape(value));
+        }
+    }
+}
+
+/// Text between the first `<name>` tag and its closing tag, with entities decoded
+///
+/// # Arguments
+///
+/// * `html` - Contents of the page
+/// * `name` - Tag name, in lowercase
+pub fn element_text(html: &str, name: &str) -> Option<String> {
+    let (_, end) = tag_spans(html, name).into_iter().next()?;
+    let start = (end + 1).min(html.len());
+    let length = html[start..]
+        .to_ascii_lowercase()
+        .find(&format!("</{}", name))?;
+    Some(unescape(html[start..start + length].trim()))
+}
+
+/// Decode the character references used in Catbox's pages
+///
+/// # Arguments
+///
+/// * `text` - Text or attribute value from a page
+pub fn unescape(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((decode_reference(&rest[1..end])?, end)));
+        match reference {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_reference(reference: &str) -> Option<char> {
+    match reference {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = reference.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes() {
+        let html = r#"<abbr title="x"><a href='one.png'>1</a><A class=link HREF=two.png>"#;
+        let hrefs: Vec<_> = tags(html, "a")
+            .filter_map(|tag| attribute(tag, "href"))
+            .collect();
+        assert_eq!(hrefs, ["one.png", "two.png"]);
+        assert_eq!(
+            attribute(
+                r#" checked content="Cats &amp; dogs &#39;&#x21;""#,
+                "content"
+            ),
+            Some("Cats & dogs '!".to_string())
+        );
+        assert_eq!(attribute(" checked", "content"), None);
+        assert_eq!(unescape("fish & chips &bogus;"), "fish & chips &bogus;");
+        assert_eq!(
+            element_text("<title>\n  Cats &lt;3\n</title>", "title"),
+            Some("Cats <3".to_string())
+        );
+    }
+}
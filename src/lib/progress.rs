@@ -0,0 +1,42 @@
+//! Progress reporting for file uploads and downloads
+//!
+//! Uploads that report progress take a callback, which is called every time
+//! a chunk of the file has been handed over to the HTTP client. Downloads call
+//! it every time a chunk has been written. To receive
+//! progress through a channel instead, send the value from the callback:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), catbox::CatboxError> {
+//! let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+//! tokio::spawn(async move {
+//!     while let Some(progress) = receiver.recv().await {
+//!         println!("{:?}", progress);
+//!     }
+//! });
+//! catbox::file::from_file_with_progress("video.mp4", None, move |progress| {
+//!     let _ = sender.send(progress);
+//! })
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+/// State of an upload or download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes sent, or received for downloads, so far
+    pub sent: u64,
+    /// Size of the whole file in bytes, or the bytes received so far if the
+    /// server didn't tell the size of a download
+    pub total: u64,
+}
+
+impl Progress {
+    /// Share of the file sent so far, between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.sent as f64 / total as f64,
+        }
+    }
+}
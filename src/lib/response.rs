@@ -0,0 +1,163 @@
+//! Helper functions for telling successful responses apart from failures
+//!
+//! Catbox answers most failed requests with HTTP 200 and a plain-text message,
+//! so the body has to be inspected in addition to the status.
+
+use std::time::{Duration, SystemTime};
+
+use reqwest::{Response, header::RETRY_AFTER};
+
+use crate::CatboxError;
+
+/// Return the body of a response, or an error if the status is not a success
+///
+/// # Arguments
+///
+/// * `response` - Response from the server
+pub async fn text(response: Response) -> Result<String, CatboxError> {
+    Ok(check(response).await?.text().await?)
+}
+
+/// Return the response, or an error with its body if the status is not a success
+///
+/// # Arguments
+///
+/// * `response` - Response from the server
+pub async fn check(response: Response) -> Result<Response, CatboxError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    Err(CatboxError::Status {
+        status,
+        body: response.text().await?,
+        retry_after,
+    })
+}
+
+/// Parse a `Retry-After` value given either as seconds or as an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    match value.trim().parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()
+            .map(|date| date.duration_since(SystemTime::now()).unwrap_or_default()),
+    }
+}
+
+/// Parse a body containing an URL with `parse`, used by uploads and album calls
+///
+/// # Arguments
+///
+/// * `body` - Body of a successful response
+/// * `parse` - Parser of the URL, e.g. one accepting URLs under the client's base URLs
+pub fn expect_url<T, E>(
+    body: String,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<T, CatboxError> {
+    let trimmed = body.trim();
+    match trimmed.starts_with("https://") || trimmed.starts_with("http://") {
+        true => parse(trimmed).map_err(|_| api_error(trimmed)),
+        false => Err(api_error(trimmed)),
+    }
+}
+
+/// Accept a body containing `message`, ignoring case
+///
+/// # Arguments
+///
+/// * `body` - Body of a successful response
+/// * `message` - Part of the expected reply
+pub fn expect_message(body: String, message: &str) -> Result<String, CatboxError> {
+    match body.to_lowercase().contains(&message.to_lowercase()) {
+        true => Ok(body),
+        false => Err(api_error(body.trim())),
+    }
+}
+
+/// Accept an empty body, the reply to a successful album deletion. Anything
+/// else is the message of a failure.
+///
+/// # Arguments
+///
+/// * `body` - Body of a successful response
+pub fn expect_empty(body: String) -> Result<String, CatboxError> {
+    match body.trim().is_empty() {
+        true => Ok(String::new()),
+        false => Err(CatboxError::Api(body.trim().to_string())),
+    }
+}
+
+fn api_error(message: &str) -> CatboxError {
+    CatboxError::Api(match message.is_empty() {
+        true => "Empty response".to_string(),
+        false => message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AlbumUrl, FileUrl};
+
+    #[test]
+    fn urls_are_accepted() {
+        let url: FileUrl = expect_url(
+            "https://files.catbox.moe/abc123.png".to_string(),
+            str::parse,
+        )
+        .unwrap();
+        assert_eq!(url.id().as_str(), "abc123.png");
+        let url: AlbumUrl =
+            expect_url("https://catbox.moe/c/abc123\n".to_string(), str::parse).unwrap();
+        assert_eq!(url.as_str(), "https://catbox.moe/c/abc123");
+    }
+
+    #[test]
+    fn messages_are_rejected() {
+        match expect_url::<AlbumUrl, _>(
+            "No album found for user specified.".to_string(),
+            str::parse,
+        ) {
+            Err(CatboxError::Api(message)) => {
+                assert_eq!(message, "No album found for user specified.")
+            },
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert!(expect_url::<FileUrl, _>(String::new(), str::parse).is_err());
+        assert!(expect_message("File doesn't exist?".to_string(), "successfully deleted").is_err());
+        assert!(expect_empty("No album found for user specified.".to_string()).is_err());
+        // Replies are told apart by their shape, not by words they contain
+        assert!(
+            expect_url::<FileUrl, _>("https://files.catbox.moe/error.png".to_string(), str::parse)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn expected_messages_are_accepted() {
+        assert!(
+            expect_message(
+                "Files successfully deleted.".to_string(),
+                "successfully deleted"
+            )
+            .is_ok()
+        );
+        assert_eq!(expect_empty("\n".to_string()).unwrap(), "");
+    }
+}
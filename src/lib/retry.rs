@@ -0,0 +1,229 @@
+//! Retrying requests that failed for transient reasons
+//!
+//! Catbox regularly responds with 5xx statuses or drops connections under load.
+//! A `RetryPolicy` set on a `CatboxClient` makes it retry such failures with an
+//! exponential backoff. Errors that won't go away by retrying, like a missing
+//! file or an error message from Catbox, are returned immediately.
+//!
+//! Uploads from readers and streams can't be retried, since their contents
+//! can only be read once. Creating and deleting albums and adding or removing
+//! their files aren't retried either: a request that failed with a server error
+//! or timeout may still have been carried out, and repeating it could create a
+//! second album or fail on the changes already made.
+
+use std::{future::Future, time::Duration};
+
+use crate::{CatboxClient, CatboxError};
+
+/// How many times and how often to retry failed requests
+///
+/// Applies to uploads of files, bytes and URLs, deleting files, reading albums
+/// and editing their title and description. Other album calls are only made once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every retry after that
+    pub initial_backoff: Duration,
+    /// Upper limit for the delay between attempts
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry up to `max_attempts - 1` times with the default backoff
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - Maximum number of attempts, including the first one
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Never retry
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    /// Delay before retrying after `attempt` failed attempts, or `None` to give up
+    ///
+    /// A `Retry-After` sent by the server is waited for if it's within `max_backoff`,
+    /// and otherwise not retried. Without one, the delay grows exponentially and is
+    /// randomized between half and all of it.
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_backoff).then_some(retry_after);
+        }
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        Some(backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0))
+    }
+}
+
+impl Default for RetryPolicy {
+    /// One attempt, 500ms initial backoff and 30s maximum backoff
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CatboxClient {
+    /// Run `request` until it succeeds, fails with an error that is not transient,
+    /// or the client's retry policy runs out of attempts
+    pub(crate) async fn with_retries<T, F, Fut>(&self, mut request: F) -> Result<T, CatboxError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CatboxError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match request().await {
+                Err(err) if err.is_transient() && attempt < self.retry_policy.max_attempts => err,
+                res => return res,
+            };
+            match self.retry_policy.delay(attempt, err.retry_after()) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(err),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{record, serve};
+
+    #[test]
+    fn backoff_grows_until_limit() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+        let first = policy.delay(1, None).unwrap();
+        assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+        let third = policy.delay(3, None).unwrap();
+        assert!(third >= Duration::from_secs(2) && third <= Duration::from_secs(4));
+        assert!(policy.delay(8, None).unwrap() <= Duration::from_secs(5));
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(4))),
+            Some(Duration::from_secs(4))
+        );
+        // Waiting longer than the policy allows is given up on
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(42))), None);
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried() {
+        let url = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: \
+             0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 35\r\nConnection: \
+             close\r\n\r\nhttps://files.catbox.moe/abc123.txt",
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        let res = client.upload_bytes("content", "file.txt").await.unwrap();
+        assert_eq!(res.id().as_str(), "abc123.txt");
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_not_waited_for() {
+        let (url, mut requests) = record(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 3600\r\nContent-Length: \
+             0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 35\r\nConnection: \
+             close\r\n\r\nhttps://files.catbox.moe/abc123.txt",
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        let err = client
+            .upload_bytes("content", "file.txt")
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3600)));
+        requests.recv().await.unwrap();
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn album_changes_are_not_retried() {
+        let (url, mut requests) = record(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: \
+             0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 26\r\nConnection: \
+             close\r\n\r\nhttps://catbox.moe/c/xyz789",
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .user_hash("hash123")
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        let files = vec!["abc123.png".parse().unwrap()];
+        assert!(matches!(
+            client.create_album("Cats", "", files).await,
+            Err(CatboxError::Status { .. })
+        ));
+        requests.recv().await.unwrap();
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let url = serve(vec![
+            "HTTP/1.1 408 Request Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 35\r\nConnection: \
+             close\r\n\r\nhttps://files.catbox.moe/abc123.txt",
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        assert!(matches!(
+            client.upload_bytes("content", "file.txt").await,
+            Err(CatboxError::Status { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn error_messages_are_not_retried() {
+        let url = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 19\r\nConnection: close\r\n\r\nFile doesn't \
+             exist?",
+            "HTTP/1.1 200 OK\r\nContent-Length: 35\r\nConnection: \
+             close\r\n\r\nhttps://files.catbox.moe/abc123.txt",
+        ])
+        .await;
+        let client = CatboxClient::builder()
+            .catbox_api_url(url)
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        assert!(matches!(
+            client.upload_bytes("content", "file.txt").await,
+            Err(CatboxError::Api(_))
+        ));
+    }
+}
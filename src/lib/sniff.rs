@@ -0,0 +1,219 @@
+//! Detecting the type of a file from its contents
+//!
+//! Catbox takes the extension of an uploaded file's URL from the name it was
+//! uploaded with, so a PNG named `screenshot` or `image.tmp` gets a link the
+//! browser can't show. With content sniffing enabled on a `CatboxClient`, the
+//! first bytes of a file are used to set its MIME type and, if asked, to append
+//! the right extension to the name it is uploaded with.
+//!
+//! Only uploads from files and memory are sniffed. Readers and streams are
+//! sent as they are.
+
+use std::io;
+
+use reqwest::multipart::Part;
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::{CatboxClient, CatboxError, helper::file_name};
+
+/// Bytes read from the start of a file to detect its type
+pub const SNIFF_LENGTH: usize = 8192;
+
+/// Extensions that are used interchangeably with the detected one
+const ALIASES: &[(&str, &str)] = &[
+    ("jpg", "jpeg"),
+    ("jpg", "jpe"),
+    ("tif", "tiff"),
+    ("gz", "tgz"),
+];
+
+/// What to do with the detected type of an uploaded file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentSniffing {
+    /// Don't look at the contents
+    #[default]
+    Off,
+    /// Send the detected MIME type with the file
+    MimeType,
+    /// Send the detected MIME type and append the right extension to the
+    /// file's name if it doesn't have it already
+    FixExtension,
+}
+
+/// Type of a file detected from its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentType {
+    /// MIME type, e.g. `image/png`
+    pub mime: &'static str,
+    /// Usual extension without the leading dot, e.g. `png`
+    pub extension: &'static str,
+}
+
+impl ContentType {
+    /// Detect the type from the first bytes of a file
+    ///
+    /// Returns `None` if the type is not recognized
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Start of the file's contents
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        infer::get(bytes).map(|kind| Self {
+            mime: kind.mime_type(),
+            extension: kind.extension(),
+        })
+    }
+
+    /// Detect the type of the file at `file_path` from its first bytes
+    ///
+    /// Returns `None` if the type is not recognized
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file
+    pub async fn detect_file(file_path: &str) -> Result<Option<Self>, io::Error> {
+        let mut file = File::open(file_path).await?;
+        let mut buf = Vec::with_capacity(SNIFF_LENGTH);
+        (&mut file)
+            .take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut buf)
+            .await?;
+        Ok(Self::detect(&buf))
+    }
+
+    /// Whether `file_name` has an extension fitting this type
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file, including the extension
+    pub fn matches(&self, file_name: &str) -> bool {
+        let extension = match file_name.rsplit_once('.') {
+            Some((_, extension)) => extension.to_lowercase(),
+            None => return false,
+        };
+        extension == self.extension
+            || ALIASES
+                .iter()
+                .any(|&(detected, alias)| detected == self.extension && alias == extension)
+    }
+
+    /// `file_name` with this type's extension appended, unless it already has a fitting one
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file, including the extension
+    pub fn fix_name(&self, file_name: &str) -> String {
+        match self.matches(file_name) {
+            true => file_name.to_string(),
+            false => format!("{}.{}", file_name, self.extension),
+        }
+    }
+}
+
+impl ContentSniffing {
+    /// Name to upload a file of type `content_type` as, and the MIME type to send with it
+    fn apply(
+        &self,
+        file_name: String,
+        content_type: Option<ContentType>,
+    ) -> (String, Option<&'static str>) {
+        match (self, content_type) {
+            (ContentSniffing::Off, _) | (_, None) => (file_name, None),
+            (ContentSniffing::MimeType, Some(content_type)) => (file_name, Some(content_type.mime)),
+            (ContentSniffing::FixExtension, Some(content_type)) => {
+                (content_type.fix_name(&file_name), Some(content_type.mime))
+            },
+        }
+    }
+}
+
+impl CatboxClient {
+    /// Name to upload the file at `file_path` as, and its MIME type if sniffing is enabled
+    pub(crate) async fn sniff_file(
+        &self,
+        file_path: &str,
+    ) -> Result<(String, Option<&'static str>), CatboxError> {
+        let content_type = match self.content_sniffing {
+            ContentSniffing::Off => None,
+            _ => ContentType::detect_file(file_path).await?,
+        };
+        Ok(self
+            .content_sniffing
+            .apply(file_name(file_path), content_type))
+    }
+
+    /// Name to upload `bytes` as, and their MIME type if sniffing is enabled
+    pub(crate) fn sniff_bytes(
+        &self,
+        bytes: &[u8],
+        file_name: String,
+    ) -> (String, Option<&'static str>) {
+        let content_type = match self.content_sniffing {
+            ContentSniffing::Off => None,
+            _ => ContentType::detect(&bytes[..bytes.len().min(SNIFF_LENGTH)]),
+        };
+        self.content_sniffing.apply(file_name, content_type)
+    }
+}
+
+/// Set the file name and, if known, the MIME type of `part`
+pub(crate) fn named_part(
+    part: Part,
+    file_name: &str,
+    mime: Option<&str>,
+) -> Result<Part, CatboxError> {
+    let part = part.file_name(file_name.to_string());
+    Ok(match mime {
+        Some(mime) => part.mime_str(mime)?,
+        None => part,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn detect() {
+        let content_type = ContentType::detect(PNG).unwrap();
+        assert_eq!(content_type.mime, "image/png");
+        assert_eq!(content_type.extension, "png");
+        assert_eq!(ContentType::detect(b"plain text"), None);
+    }
+
+    #[test]
+    fn fix_names() {
+        let png = ContentType::detect(PNG).unwrap();
+        assert!(png.matches("image.PNG"));
+        assert!(!png.matches("image.tmp"));
+        assert_eq!(png.fix_name("screenshot"), "screenshot.png");
+        assert_eq!(png.fix_name("image.tmp"), "image.tmp.png");
+        assert_eq!(png.fix_name("image.png"), "image.png");
+
+        let jpg = ContentType::detect(b"\xff\xd8\xff\xe0\0\x10JFIF").unwrap();
+        assert!(jpg.matches("photo.jpeg"));
+    }
+
+    #[test]
+    fn sniffing_modes() {
+        let png = ContentType::detect(PNG);
+        assert_eq!(
+            ContentSniffing::Off.apply("a".to_string(), png),
+            ("a".to_string(), None)
+        );
+        assert_eq!(
+            ContentSniffing::MimeType.apply("a".to_string(), png),
+            ("a".to_string(), Some("image/png"))
+        );
+        assert_eq!(
+            ContentSniffing::FixExtension.apply("a".to_string(), png),
+            ("a.png".to_string(), Some("image/png"))
+        );
+        assert_eq!(
+            ContentSniffing::FixExtension.apply("a".to_string(), None),
+            ("a".to_string(), None)
+        );
+    }
+}
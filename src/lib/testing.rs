@@ -0,0 +1,181 @@
+//! Local HTTP server for tests
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, UnboundedReceiver},
+};
+
+/// Request received by the server
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// Request line and headers
+    pub head: String,
+    /// Body, with any chunked encoding removed
+    pub body: Vec<u8>,
+}
+
+/// Part of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub name: String,
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+    pub value: String,
+}
+
+impl Request {
+    /// Request line, e.g. `POST / HTTP/1.1`
+    pub fn line(&self) -> &str {
+        self.head.lines().next().unwrap_or_default()
+    }
+
+    /// Value of the header `name`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.head, name)
+    }
+
+    /// Parts of a `multipart/form-data` body
+    pub fn parts(&self) -> Vec<Part> {
+        let boundary = self
+            .header("content-type")
+            .and_then(|value| value.split_once("boundary="))
+            .map(|(_, boundary)| format!("--{}", boundary.trim_matches('"')))
+            .expect("not a multipart request");
+        let body = String::from_utf8_lossy(&self.body);
+        body.split(boundary.as_str())
+            .filter_map(|part| {
+                let (head, value) = part.strip_prefix("\r\n")?.split_once("\r\n\r\n")?;
+                let disposition = header(head, "content-disposition")?;
+                Some(Part {
+                    name: parameter(disposition, "name")?,
+                    file_name: parameter(disposition, "filename"),
+                    content_type: header(head, "content-type").map(str::to_string),
+                    value: value.strip_suffix("\r\n").unwrap_or(value).to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Names and values of the parts of a `multipart/form-data` body
+    pub fn fields(&self) -> Vec<(String, String)> {
+        self.parts()
+            .into_iter()
+            .map(|part| (part.name, part.value))
+            .collect()
+    }
+}
+
+/// Serve one raw HTTP response per connection, in order
+///
+/// Returns the URL of the server
+pub async fn serve<S: Into<String> + Send + 'static>(responses: Vec<S>) -> String {
+    record(responses).await.0
+}
+
+/// Serve one raw HTTP response per connection, in order, keeping the requests
+///
+/// Returns the URL of the server, and the requests as they're received
+pub async fn record<S: Into<String> + Send + 'static>(
+    responses: Vec<S>,
+) -> (String, UnboundedReceiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = sender.send(read_request(&mut socket).await);
+            let _ = socket.write_all(response.into().as_bytes()).await;
+            let _ = socket.shutdown().await;
+        }
+    });
+    (url, receiver)
+}
+
+/// `200 OK` response with `body`
+pub fn ok(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Read a request until its body is complete, or the client stops sending
+async fn read_request(socket: &mut TcpStream) -> Request {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        if let Some(request) = complete_request(&data) {
+            return request;
+        }
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => data.extend_from_slice(&buf[..read]),
+        }
+    }
+    let end = find(&data, b"\r\n\r\n").unwrap_or(data.len());
+    Request {
+        head: String::from_utf8_lossy(&data[..end]).to_string(),
+        body: data.get(end + 4..).unwrap_or_default().to_vec(),
+    }
+}
+
+/// The request in `data`, if all of it has been received
+fn complete_request(data: &[u8]) -> Option<Request> {
+    let end = find(data, b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&data[..end]).to_string();
+    let rest = &data[end + 4..];
+    let body = match (
+        header(&head, "content-length"),
+        header(&head, "transfer-encoding"),
+    ) {
+        (Some(length), _) => rest.get(..length.parse().ok()?)?.to_vec(),
+        (None, Some(encoding)) if encoding.eq_ignore_ascii_case("chunked") => dechunk(rest)?,
+        _ => Vec::new(),
+    };
+    Some(Request { head, body })
+}
+
+/// Decode a chunked body, if its last chunk has been received
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = find(data, b"\r\n")?;
+        let size = String::from_utf8_lossy(&data[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        match key.trim().eq_ignore_ascii_case(name) {
+            true => Some(value.trim()),
+            false => None,
+        }
+    })
+}
+
+/// Value of a parameter of a header, e.g. `name` of `form-data; name="userhash"`
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        match key.trim() == name {
+            true => Some(value.trim().trim_matches('"').to_string()),
+            false => None,
+        }
+    })
+}
@@ -0,0 +1,485 @@
+//! Typed values for IDs and URLs returned and accepted by the API
+//!
+//! All types can be parsed from both bare IDs and full URLs, e.g.
+//! `abc123.png`, `https://files.catbox.moe/abc123.png` and
+//! `www.files.catbox.moe/abc123.png/?download` all give the file ID `abc123.png`.
+//! URLs must have the path of their kind: a file is a single segment with an
+//! extension, and an album is `/c/` and its ID, so an album URL is never taken
+//! for a file. Any host is accepted, for instances laid out like Catbox. For
+//! instances serving files or albums from other paths, the `parse_with`
+//! constructors also accept URLs under the given base URL.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use url::Url;
+
+use crate::{CATBOX_ALBUM_URL, CATBOX_FILES_URL};
+
+/// Error returned when a string is not a valid ID or URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError(String);
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not a valid ID or URL", self.0)
+    }
+}
+
+impl Error for ParseIdError {}
+
+/// Name of a file on Catbox or Litterbox, e.g. `abc123.png`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileId(String);
+
+/// ID of an album, e.g. `abc123`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AlbumShort(String);
+
+/// Full URL to a file, e.g. `https://files.catbox.moe/abc123.png`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileUrl {
+    url: String,
+    id: FileId,
+}
+
+/// Full URL to an album, e.g. `https://catbox.moe/c/abc123`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AlbumUrl {
+    url: String,
+    short: AlbumShort,
+}
+
+impl FileId {
+    /// Parse a file ID, or the URL of a file under `files_url` or on Catbox
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Bare ID or URL
+    /// * `files_url` - Base URL files are served from, e.g. `https://files.catbox.moe/`
+    pub fn parse_with(s: &str, files_url: &str) -> Result<Self, ParseIdError> {
+        parse(s, files_url, Kind::File).map(|(_, id)| FileId(id))
+    }
+
+    /// The ID as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AlbumShort {
+    /// Parse an album ID, or the URL of an album under `album_url` or on Catbox
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Bare ID or URL
+    /// * `album_url` - Base URL albums are served from, e.g. `https://catbox.moe/c/`
+    pub fn parse_with(s: &str, album_url: &str) -> Result<Self, ParseIdError> {
+        parse(s, album_url, Kind::Album).map(|(_, short)| AlbumShort(short))
+    }
+
+    /// The ID as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FileUrl {
+    /// Create an URL to a file under `base_url`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - Base URL files are served from, e.g. `https://files.catbox.moe/`
+    /// * `id` - Name of the file
+    pub fn new(base_url: &str, id: FileId) -> Self {
+        Self {
+            url: format!("{}/{}", base_url.trim_end_matches('/'), id),
+            id,
+        }
+    }
+
+    /// Parse the URL of a file under `files_url` or on Catbox. Bare IDs are
+    /// taken as files under `files_url`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Bare ID or URL
+    /// * `files_url` - Base URL files are served from, e.g. `https://files.catbox.moe/`
+    pub fn parse_with(s: &str, files_url: &str) -> Result<Self, ParseIdError> {
+        Ok(match parse(s, files_url, Kind::File)? {
+            (Some(url), id) => FileUrl {
+                url,
+                id: FileId(id),
+            },
+            (None, id) => FileUrl::new(files_url, FileId(id)),
+        })
+    }
+
+    /// The URL as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// Name of the file
+    pub fn id(&self) -> &FileId {
+        &self.id
+    }
+}
+
+impl AlbumUrl {
+    /// Create an URL to an album under `base_url`
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - Base URL albums are served from, e.g. `https://catbox.moe/c/`
+    /// * `short` - ID of the album
+    pub fn new(base_url: &str, short: AlbumShort) -> Self {
+        Self {
+            url: format!("{}/{}", base_url.trim_end_matches('/'), short),
+            short,
+        }
+    }
+
+    /// Parse the URL of an album under `album_url` or on Catbox. Bare IDs are
+    /// taken as albums under `album_url`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Bare ID or URL
+    /// * `album_url` - Base URL albums are served from, e.g. `https://catbox.moe/c/`
+    pub fn parse_with(s: &str, album_url: &str) -> Result<Self, ParseIdError> {
+        Ok(match parse(s, album_url, Kind::Album)? {
+            (Some(url), short) => AlbumUrl {
+                url,
+                short: AlbumShort(short),
+            },
+            (None, short) => AlbumUrl::new(album_url, AlbumShort(short)),
+        })
+    }
+
+    /// The URL as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// ID of the album
+    pub fn short(&self) -> &AlbumShort {
+        &self.short
+    }
+}
+
+impl FromStr for FileId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FileId::parse_with(s, CATBOX_FILES_URL)
+    }
+}
+
+impl FromStr for AlbumShort {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AlbumShort::parse_with(s, CATBOX_ALBUM_URL)
+    }
+}
+
+impl FromStr for FileUrl {
+    type Err = ParseIdError;
+
+    /// Bare IDs are assumed to be files on `files.catbox.moe`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FileUrl::parse_with(s, CATBOX_FILES_URL)
+    }
+}
+
+impl FromStr for AlbumUrl {
+    type Err = ParseIdError;
+
+    /// Bare IDs are assumed to be albums on `catbox.moe`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AlbumUrl::parse_with(s, CATBOX_ALBUM_URL)
+    }
+}
+
+/// What the path of an URL must look like, and what a bare ID must be, for each type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A single segment with an extension, e.g. `/abc123.png`
+    File,
+    /// `/c/` followed by an ID without an extension, e.g. `/c/abc123`
+    Album,
+}
+
+impl Kind {
+    /// Segments of the path before the ID on Catbox
+    fn prefix(self) -> Vec<String> {
+        match self {
+            Kind::File => vec![],
+            Kind::Album => vec!["c".to_string()],
+        }
+    }
+
+    fn accepts(self, id: &str) -> bool {
+        is_valid_id(id)
+            && match self {
+                Kind::File => id
+                    .rsplit_once('.')
+                    .is_some_and(|(stem, extension)| !stem.is_empty() && !extension.is_empty()),
+                Kind::Album => !id.contains('.'),
+            }
+    }
+}
+
+/// Split the input into a normalized URL, if it was one, and its ID
+///
+/// URLs are accepted if their path is the ID under the path of `base_url`, on
+/// the same host, or if it has the shape of `kind` on Catbox, on any host.
+fn parse(s: &str, base_url: &str, kind: Kind) -> Result<(Option<String>, String), ParseIdError> {
+    let input = s.trim();
+    let err = || ParseIdError(input.to_string());
+    if !input.contains('/') {
+        let id = input.split(['?', '#']).next().unwrap_or_default();
+        return match kind.accepts(id) {
+            true => Ok((None, id.to_string())),
+            false => Err(err()),
+        };
+    }
+
+    let url = parse_url(input).ok_or_else(err)?;
+    let segments = path_segments(&url);
+    let id = [base_prefix(&url, base_url), Some(kind.prefix())]
+        .into_iter()
+        .flatten()
+        .find_map(|prefix| match segments.strip_prefix(prefix.as_slice()) {
+            Some([id]) if kind.accepts(id) => Some(id.clone()),
+            _ => None,
+        })
+        .ok_or_else(err)?;
+    Ok((
+        Some(format!(
+            "{}/{}",
+            url.origin().ascii_serialization(),
+            segments.join("/")
+        )),
+        id,
+    ))
+}
+
+/// ID of the file at `url`, if it's directly under `files_url`.
+/// Only the host is compared, so http and https links are both accepted.
+pub(crate) fn file_under(url: &Url, files_url: &str) -> Option<FileId> {
+    let segments = path_segments(url);
+    match segments.strip_prefix(base_prefix(url, files_url)?.as_slice()) {
+        Some([id]) if Kind::File.accepts(id) => Some(FileId(id.clone())),
+        _ => None,
+    }
+}
+
+/// Parse an http or https URL, adding the scheme if it's missing and dropping
+/// the query and fragment
+fn parse_url(input: &str) -> Option<Url> {
+    let with_scheme = match input.contains("://") {
+        true => input.to_string(),
+        false => format!("https://{}", input),
+    };
+    let mut url = Url::parse(&with_scheme).ok()?;
+    if !["http", "https"].contains(&url.scheme()) {
+        return None;
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url)
+}
+
+/// Non-empty segments of the path of `url`
+fn path_segments(url: &Url) -> Vec<String> {
+    url.path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Segments of the path of `base_url`, if `url` is on its host, ignoring `www.`
+fn base_prefix(url: &Url, base_url: &str) -> Option<Vec<String>> {
+    let base = parse_url(base_url)?;
+    let host = |url: &Url| {
+        url.host_str()
+            .map(|host| host.trim_start_matches("www.").to_string())
+    };
+    match host(url).is_some() && host(url) == host(&base) {
+        true => Some(path_segments(&base)),
+        false => None,
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ['.', '-', '_'].contains(&c))
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for FileId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AlbumShort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for AlbumShort {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for FileUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl AsRef<str> for FileUrl {
+    fn as_ref(&self) -> &str {
+        &self.url
+    }
+}
+
+impl fmt::Display for AlbumUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+impl AsRef<str> for AlbumUrl {
+    fn as_ref(&self) -> &str {
+        &self.url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_ids() {
+        assert_eq!("abc123.png".parse(), Ok(FileId("abc123.png".to_string())));
+        assert_eq!(" abc123 \n".parse(), Ok(AlbumShort("abc123".to_string())));
+        assert!("not an id".parse::<FileId>().is_err());
+        assert!("".parse::<FileId>().is_err());
+    }
+
+    #[test]
+    fn ids_from_urls() {
+        for input in [
+            "https://files.catbox.moe/abc123.png",
+            "http://files.catbox.moe/abc123.png",
+            "files.catbox.moe/abc123.png",
+            "https://www.files.catbox.moe/abc123.png/",
+            "https://files.catbox.moe/abc123.png?download=1",
+            "https://litter.catbox.moe/abc123.png\n",
+        ] {
+            assert_eq!(input.parse::<FileId>().unwrap().as_str(), "abc123.png");
+        }
+        assert_eq!(
+            "https://catbox.moe/c/xyz789/"
+                .parse::<AlbumShort>()
+                .unwrap()
+                .as_str(),
+            "xyz789"
+        );
+        assert!(
+            "ftp://files.catbox.moe/abc123.png"
+                .parse::<FileId>()
+                .is_err()
+        );
+        assert!("https://files.catbox.moe/".parse::<FileId>().is_err());
+    }
+
+    #[test]
+    fn wrong_kinds() {
+        // An album is never taken for a file, nor a file for an album
+        assert!("https://catbox.moe/c/abc123".parse::<FileId>().is_err());
+        assert!(
+            "https://files.catbox.moe/abc123.png"
+                .parse::<AlbumShort>()
+                .is_err()
+        );
+        assert!("abc123".parse::<FileId>().is_err());
+        assert!("abc123.png".parse::<AlbumShort>().is_err());
+        assert!(
+            "https://files.catbox.moe/dir/abc123.png"
+                .parse::<FileId>()
+                .is_err()
+        );
+        assert!("https://catbox.moe/abc123".parse::<AlbumShort>().is_err());
+        assert!(
+            "https://catbox.moe/x/c/abc123"
+                .parse::<AlbumShort>()
+                .is_err()
+        );
+        assert_eq!(
+            "https://example.com/abc123.png".parse::<FileId>(),
+            Ok(FileId("abc123.png".to_string()))
+        );
+    }
+
+    #[test]
+    fn custom_base_urls() {
+        let files = "https://example.com/files/";
+        assert_eq!(
+            FileId::parse_with("http://www.example.com/files/abc123.png", files),
+            Ok(FileId("abc123.png".to_string()))
+        );
+        assert!(FileId::parse_with("https://example.com/other/abc123.png", files).is_err());
+        assert!(
+            "https://example.com/files/abc123.png"
+                .parse::<FileId>()
+                .is_err()
+        );
+        assert_eq!(
+            FileUrl::parse_with("abc123.png", files).unwrap().as_str(),
+            "https://example.com/files/abc123.png"
+        );
+
+        let albums = "https://example.com/albums/";
+        assert_eq!(
+            AlbumShort::parse_with("example.com/albums/xyz789", albums),
+            Ok(AlbumShort("xyz789".to_string()))
+        );
+        assert_eq!(
+            AlbumShort::parse_with("https://catbox.moe/c/xyz789", albums),
+            Ok(AlbumShort("xyz789".to_string()))
+        );
+        assert_eq!(
+            AlbumUrl::parse_with("xyz789", albums).unwrap().as_str(),
+            "https://example.com/albums/xyz789"
+        );
+    }
+
+    #[test]
+    fn urls() {
+        let url: FileUrl = "www.files.catbox.moe/abc123.png/?x=1".parse().unwrap();
+        assert_eq!(url.as_str(), "https://www.files.catbox.moe/abc123.png");
+        assert_eq!(url.id().as_str(), "abc123.png");
+
+        let url: FileUrl = "abc123.png".parse().unwrap();
+        assert_eq!(url.as_str(), "https://files.catbox.moe/abc123.png");
+
+        let url: AlbumUrl = "xyz789".parse().unwrap();
+        assert_eq!(url.as_str(), "https://catbox.moe/c/xyz789");
+        assert_eq!(url.short().as_str(), "xyz789");
+    }
+}
@@ -1,255 +1,1271 @@
-use std::{env, error::Error, path::Path};
+use std::{
+    collections::HashSet,
+    env,
+    error::Error,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use args::{
-    Album, AlbumAdd, AlbumCommand, AlbumCreate, AlbumDelete, AlbumEdit, AlbumRemove, CatboxArgs,
-    CatboxCommand, Delete, Litter, Upload,
+    Album, AlbumAdd, AlbumCommand, AlbumCreate, AlbumDelete, AlbumEdit, AlbumRemove, AlbumShow,
+    AlbumSync, AlbumUpdate, CatboxArgs, CatboxCommand, ClientArgs, Config, ConfigCommand, Delete,
+    Download, History, HistoryCommand, Litter, Login, Logout, Upload, WalkArgs,
 };
 
-use clap::Parser;
-use futures::stream::{FuturesUnordered, StreamExt};
-use regex::Regex;
+use clap::{Parser, ValueEnum};
+use config::Profile;
+use download::{Source, Targets};
+use exit::{Exit, ExitError};
+use futures::{
+    Future,
+    future::{self, Either},
+    stream::{self, StreamExt, TryStreamExt},
+};
+use output::{OutputFormat, Report, Status};
+use progress::UploadBars;
+use sync::{Plan, State, Synced};
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
 use url::Url;
+use walk::{Symlinks, Walker};
 
 #[cfg(not(test))]
-use catbox::{album, file, litter};
+use catbox::CatboxClient;
+use catbox::{
+    AlbumChanges, AlbumShort, AlbumUrl, CatboxError, ContentSniffing, ContentType, FileId, FileUrl,
+    LitterExpiry, RetryPolicy, UploadLimits, history::Entry, sniff::SNIFF_LENGTH,
+};
 #[cfg(test)]
 mod test;
 #[cfg(test)]
-use test::catbox::{album, file, litter};
+use test::catbox::CatboxClient;
 
 mod args;
+mod config;
+mod download;
+mod exit;
+mod history;
+mod output;
+mod progress;
+mod sync;
+mod walk;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    match CatboxArgs::try_parse() {
+async fn main() -> ExitCode {
+    let res = match CatboxArgs::try_parse() {
         Ok(args) => match args.command {
             CatboxCommand::Upload(sub_args) => upload(sub_args).await,
             CatboxCommand::Delete(sub_args) => delete_file(sub_args).await,
             CatboxCommand::Album(sub_args) => parse_album(sub_args).await,
             CatboxCommand::Litter(sub_args) => litter(sub_args).await,
+            CatboxCommand::Download(sub_args) => download(sub_args).await,
+            CatboxCommand::History(sub_args) => parse_history(sub_args).await,
+            CatboxCommand::Login(sub_args) => login(sub_args),
+            CatboxCommand::Logout(sub_args) => logout(sub_args),
+            CatboxCommand::Config(sub_args) => parse_config(sub_args),
         },
-        Err(args) => {
-            println!("{}", args);
-            Ok(())
+        Err(err) => {
+            // Help and version are printed to stdout, errors to stderr
+            let _ = err.print();
+            return match err.use_stderr() {
+                true => Exit::Usage.into(),
+                false => ExitCode::SUCCESS,
+            };
+        },
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let message = err.to_string();
+            if !message.is_empty() {
+                eprintln!("Error: {}", message);
+            }
+            exit::exit_code(err.as_ref()).into()
         },
     }
 }
 
+/// Input read from stdin instead of a file
+static STDIN: &str = "-";
+
+/// Uploads run at the same time unless configured otherwise
+static DEFAULT_CONCURRENCY: usize = 10;
+
 fn user_hash_from_env() -> Option<String> {
     env::var("CATBOX_USER_HASH").ok()
 }
 
-fn catbox_url_to_image_name<'a>(url: &'a str) -> String {
-    let re = Regex::new(r"^(http[s]?://)?files.catbox.moe/.+").unwrap();
-    match re.is_match(url) {
-        true => url.split("/").last().unwrap().to_string(),
-        false => url.to_string(),
-    }
+/// User hash from the argument, environment variable or profile
+fn user_hash(user_hash: Option<String>, profile: &Profile) -> Option<String> {
+    user_hash
+        .or_else(user_hash_from_env)
+        .or_else(|| profile.user_hash.clone())
+}
+
+/// User hash for a command that can't work without one
+fn require_user_hash(user_hash: Option<String>, profile: &Profile) -> Result<String, ExitError> {
+    self::user_hash(user_hash, profile).ok_or_else(|| {
+        ExitError::new(
+            Exit::Auth,
+            "This command requires a user hash. Give it with --user or CATBOX_USER_HASH, or save \
+             it with `catbox login`.",
+        )
+    })
+}
+
+/// Parse IDs or URLs of files, accepting URLs under the client's files URL
+fn file_ids(client: &CatboxClient, inputs: &[String]) -> Result<Vec<FileId>, ExitError> {
+    inputs
+        .iter()
+        .map(|input| {
+            FileId::parse_with(input, client.files_url())
+                .map_err(|err| ExitError::new(Exit::Usage, err.to_string()))
+        })
+        .collect()
 }
 
-fn album_url_to_short<'a>(url: &'a str) -> String {
-    let re = Regex::new(r"^(http[s]?://)?catbox.moe/c/.+").unwrap();
-    match re.is_match(url) {
-        true => url.split("/").last().unwrap().to_string(),
-        false => url.to_string(),
+/// Parse the ID or URL of an album, accepting URLs under the client's album URL
+fn album_short(client: &CatboxClient, input: &str) -> Result<AlbumShort, ExitError> {
+    AlbumShort::parse_with(input, client.album_url())
+        .map_err(|err| ExitError::new(Exit::Usage, err.to_string()))
+}
+
+/// Build a client shared by every request of a command.
+/// Explicitly given arguments are preferred over environment variables,
+/// and both over the selected profile of the config file.
+fn client(user_hash: Option<String>, client_args: &ClientArgs, profile: &Profile) -> CatboxClient {
+    let setting = |arg: &Option<String>, var: &str, value: &Option<String>| {
+        arg.clone()
+            .or_else(|| env::var(var).ok())
+            .or_else(|| value.clone())
+    };
+    let mut builder = CatboxClient::builder();
+    if let Some(hash) = self::user_hash(user_hash, profile) {
+        builder = builder.user_hash(hash);
+    }
+    if let Some(url) = setting(&client_args.api_url, "CATBOX_API_URL", &profile.api_url) {
+        builder = builder.catbox_api_url(url);
+    }
+    if let Some(url) = setting(
+        &client_args.litter_api_url,
+        "LITTER_API_URL",
+        &profile.litter_api_url,
+    ) {
+        builder = builder.litter_api_url(url);
+    }
+    if let Some(url) = setting(
+        &client_args.files_url,
+        "CATBOX_FILES_URL",
+        &profile.files_url,
+    ) {
+        builder = builder.files_url(url);
+    }
+    if let Some(url) = setting(
+        &client_args.album_url,
+        "CATBOX_ALBUM_URL",
+        &profile.album_url,
+    ) {
+        builder = builder.album_url(url);
+    }
+    if let Some(retries) = client_args
+        .retries
+        .or_else(|| env_setting("CATBOX_RETRIES"))
+        .or(profile.retries)
+    {
+        builder = builder.retry_policy(RetryPolicy::new(retries.saturating_add(1)));
+    }
+    if let Some(timeout) = client_args
+        .timeout
+        .or_else(|| env_setting("CATBOX_TIMEOUT"))
+        .or(profile.timeout)
+    {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+    if client_args.no_limits {
+        builder = builder.upload_limits(UploadLimits::none());
+    }
+    if client_args.fix_extension {
+        builder = builder.content_sniffing(ContentSniffing::FixExtension);
+    } else if client_args.sniff {
+        builder = builder.content_sniffing(ContentSniffing::MimeType);
+    }
+    if !client_args.no_history {
+        if let Some(path) = catbox::History::default_path() {
+            builder = builder.history(catbox::History::new(path));
+        }
     }
+    builder.build()
+}
+
+/// Format of the results: the argument, environment variable, profile or text
+fn output_format(client_args: &ClientArgs, profile: &Profile) -> OutputFormat {
+    client_args
+        .output
+        .or_else(|| env_setting("CATBOX_OUTPUT"))
+        .or(profile.output)
+        .unwrap_or_default()
+}
+
+/// Value of the environment variable `var`, if it is set and valid
+fn env_setting<T: FromStr>(var: &str) -> Option<T> {
+    env::var(var).ok()?.parse().ok()
+}
+
+/// Number of uploads to run at the same time
+fn concurrency(client_args: &ClientArgs, profile: &Profile) -> usize {
+    client_args
+        .concurrency
+        .or_else(|| env_setting("CATBOX_CONCURRENCY"))
+        .or(profile.concurrency)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .max(1)
 }
 
 async fn parse_album(album_args: Album) -> Result<(), Box<dyn Error>> {
     match album_args.album_command {
+        AlbumCommand::Show(sub_args) => show_album(sub_args).await,
         AlbumCommand::Create(sub_args) => create_album(sub_args).await,
         AlbumCommand::Delete(sub_args) => delete_album(sub_args).await,
         AlbumCommand::Edit(sub_args) => edit_album(sub_args).await,
+        AlbumCommand::Update(sub_args) => update_album(sub_args).await,
         AlbumCommand::Add(sub_args) => add_to_album(sub_args).await,
         AlbumCommand::Remove(sub_args) => remove_from_album(sub_args).await,
+        AlbumCommand::Sync(sub_args) => sync_album(sub_args).await,
     }
 }
 
-async fn upload(upload_args: Upload) -> Result<(), Box<dyn Error>> {
-    let (files, rest): (Vec<_>, _) = upload_args
-        .files
-        .into_iter()
-        .partition(|uri| Path::new(&uri).exists());
-    let (urls, rest): (Vec<_>, _) = rest.iter().partition(|uri| Url::parse(uri).is_ok());
-    let env_user = user_hash_from_env();
-    let user = upload_args.user_hash.or(env_user);
-    let print_result = |res| async move { println!("{}", res) };
-
-    tokio::join!(
-        rest.into_iter()
-            .map(|uri| invalid_uri(uri.to_string()))
-            .collect::<FuturesUnordered<_>>()
-            .for_each_concurrent(10, print_result),
-        urls.into_iter()
-            .map(|url| upload_url(url.to_string(), &user))
-            .collect::<FuturesUnordered<_>>()
-            .for_each_concurrent(10, print_result),
-        files
-            .into_iter()
-            .map(|file| upload_file(file, &user))
-            .collect::<FuturesUnordered<_>>()
-            .for_each_concurrent(10, print_result),
+/// Entries with their numbers in the history, starting from 1
+fn numbered<'a>(entries: impl Iterator<Item = (usize, &'a Entry)>) -> Vec<(usize, Entry)> {
+    entries
+        .map(|(index, entry)| (index + 1, entry.clone()))
+        .collect()
+}
+
+async fn parse_history(history_args: History) -> Result<(), Box<dyn Error>> {
+    let path = catbox::History::default_path().ok_or("No data directory for the history")?;
+    let recorded = catbox::History::new(path).entries()?;
+    let entries = numbered(recorded.iter().enumerate());
+    let matching = |filter: catbox::history::Filter| numbered(filter.select(&recorded).into_iter());
+    let output = match history_args.history_command {
+        HistoryCommand::List(sub_args) => {
+            let entries = matching(history::filter(sub_args.filter, None));
+            let skip = sub_args
+                .limit
+                .map_or(0, |limit| entries.len().saturating_sub(limit));
+            history::list(&entries[skip..])
+        },
+        HistoryCommand::Search(sub_args) => history::list(&matching(history::filter(
+            sub_args.filter,
+            Some(sub_args.query),
+        ))),
+        HistoryCommand::Show(sub_args) => history::find(&entries, &sub_args.entry)
+            .map(|(number, entry)| history::show(*number, entry))
+            .ok_or_else(|| format!("No entry {} in the history", sub_args.entry))?,
+        HistoryCommand::Export(sub_args) => history::export(
+            &matching(history::filter(sub_args.filter, None)),
+            sub_args.format,
+        )?,
+    };
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+fn login(login_args: Login) -> Result<(), Box<dyn Error>> {
+    let user_hash = match login_args.user_hash {
+        Some(user_hash) => user_hash,
+        None => {
+            eprint!("User hash: ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line
+        },
+    };
+    let user_hash = user_hash.trim();
+    if user_hash.is_empty() {
+        return Err("No user hash given".into());
+    }
+    let (path, name) = config::edit_profile(&login_args.client_args, |profile| {
+        profile.user_hash = Some(user_hash.to_string());
+        Ok(())
+    })?;
+    println!(
+        "Saved the user hash to profile {} in {}",
+        name,
+        path.display()
     );
     Ok(())
 }
 
-async fn invalid_uri(uri: String) -> String {
-    format!("Ignoring {}: Not a file or valid URL", uri)
+fn logout(logout_args: Logout) -> Result<(), Box<dyn Error>> {
+    let mut had_user_hash = false;
+    let (path, name) = config::edit_profile(&logout_args.client_args, |profile| {
+        had_user_hash = profile.user_hash.take().is_some();
+        Ok(())
+    })?;
+    match had_user_hash {
+        true => println!(
+            "Removed the user hash from profile {} in {}",
+            name,
+            path.display()
+        ),
+        false => println!("Profile {} has no user hash", name),
+    }
+    Ok(())
 }
 
-async fn upload_file(file: String, user_hash: &Option<String>) -> String {
-    match file::from_file(file.clone(), user_hash.as_ref().cloned()).await {
-        Ok(res) => res,
-        Err(_) => format!("Uploading {} failed.", &file),
+fn parse_config(config_args: Config) -> Result<(), Box<dyn Error>> {
+    match config_args.config_command {
+        ConfigCommand::Get(sub_args) => {
+            if let Some(value) = config::load_profile(&sub_args.client_args)?.get(sub_args.key) {
+                println!("{}", value);
+            }
+        },
+        ConfigCommand::Set(sub_args) => {
+            config::edit_profile(&sub_args.client_args, |profile| {
+                profile.set(sub_args.key, Some(&sub_args.value))
+            })?;
+        },
+        ConfigCommand::Unset(sub_args) => {
+            config::edit_profile(&sub_args.client_args, |profile| {
+                profile.set(sub_args.key, None)
+            })?;
+        },
+        ConfigCommand::List(sub_args) => {
+            let profile = config::load_profile(&sub_args.client_args)?;
+            for key in config::Key::value_variants() {
+                if let Some(value) = profile.get(*key) {
+                    let value = match key {
+                        config::Key::UserHash => config::masked(&value),
+                        _ => value,
+                    };
+                    println!("{} = {}", key, value);
+                }
+            }
+        },
     }
+    Ok(())
 }
 
-async fn upload_url(url: String, user_hash: &Option<String>) -> String {
-    match file::from_url(&url, user_hash.clone().as_ref()).await {
-        Ok(res) => res,
-        Err(_) => format!("Uploading {} failed.", url),
+async fn upload(upload_args: Upload) -> Result<(), Box<dyn Error>> {
+    check_stdin(&upload_args.files, &upload_args.name)?;
+    let inputs = expand_inputs(upload_args.files, &upload_args.walk_args);
+    let profile = config::load_profile(&upload_args.client_args)?;
+    let client = client(upload_args.user_hash, &upload_args.client_args, &profile);
+    let concurrency = concurrency(&upload_args.client_args, &profile);
+    let output = output_format(&upload_args.client_args, &profile);
+    let bars = UploadBars::new(&existing_files(&inputs));
+
+    let upload_input = |input: Input| {
+        let name = upload_args.name.clone();
+        let (client, bars) = (&client, &bars);
+        async move {
+            match input {
+                Input::Uri(uri) => upload_uri(uri, name, client, bars).await,
+                Input::Done(report) => report,
+            }
+        }
+    };
+    let (reports, album) = match upload_args.album {
+        Some(title) => {
+            if let Some(header) = output.header() {
+                bars.println(&header);
+            }
+            let upload = |input| {
+                let (upload_input, bars) = (&upload_input, &bars);
+                async move {
+                    let report = upload_input(input).await;
+                    if let Some(line) = output.line(&report) {
+                        bars.println(&line);
+                    }
+                    report
+                }
+            };
+            let description = upload_args.description.unwrap_or_default();
+            let (reports, album) =
+                upload_album(title, description, inputs, upload, concurrency, &client).await;
+            (reports, Some(album))
+        },
+        None => {
+            let uploads = inputs.into_iter().map(upload_input);
+            let reports = run_reports(uploads, concurrency, output, &bars).await;
+            (reports, None)
+        },
+    };
+    bars.finish();
+
+    if let Some(line) = album.as_ref().and_then(|album| output.line(album)) {
+        println!("{}", line);
+    }
+    let all_reports: Vec<_> = reports.iter().chain(&album).cloned().collect();
+    if let Some(footer) = output.footer(&all_reports) {
+        println!("{}", footer);
+    }
+    match album {
+        Some(album) if album.status == Status::Failed => {
+            Err(ExitError::silent(album.exit()).into())
+        },
+        _ => Ok(exit::check_batch(&reports)?),
     }
 }
 
-async fn upload_to_litter(file_path: String, time: u8) -> String {
-    match litter::upload(&file_path, time).await {
-        Ok(res) => res,
-        Err(_) => format!("Uploading {} failed.", file_path),
+/// Upload `inputs` with `upload` and create an album of the uploaded files,
+/// in the order they were given.
+/// Returns the reports of the uploads in the order of the inputs, and of the album.
+async fn upload_album<F, Fut>(
+    title: String,
+    description: String,
+    inputs: Vec<Input>,
+    upload: F,
+    concurrency: usize,
+    client: &CatboxClient,
+) -> (Vec<Report>, Report)
+where
+    F: Fn(Input) -> Fut,
+    Fut: Future<Output = Report>,
+{
+    let started = Instant::now();
+    let reports = Mutex::new(Vec::new());
+    let inputs: Vec<_> = inputs.into_iter().enumerate().collect();
+    let res = client
+        .create_album_from_uploads(
+            title.clone(),
+            description,
+            inputs,
+            concurrency,
+            |(index, input)| {
+                let (upload, reports) = (&upload, &reports);
+                async move {
+                    let report = upload(input).await;
+                    let res = match (report.status, &report.url) {
+                        (Status::Ok, Some(url)) => FileUrl::parse_with(url, client.files_url())
+                            .map_err(|err| CatboxError::Api(err.to_string())),
+                        _ => Err(CatboxError::Api(report.error.clone().unwrap_or_default())),
+                    };
+                    reports.lock().unwrap().push((index, report));
+                    res
+                }
+            },
+        )
+        .await;
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|(index, _)| *index);
+    let reports: Vec<_> = reports.into_iter().map(|(_, report)| report).collect();
+
+    let album = Report::new("Creating album", title, started);
+    let album = match res {
+        Ok(uploaded) => {
+            if !uploaded.failed.is_empty() {
+                let left_out: Vec<_> = uploaded
+                    .failed
+                    .iter()
+                    .map(|((index, _), _)| reports[*index].input.as_str())
+                    .collect();
+                eprintln!("Left out of the album: {}", left_out.join(", "));
+            }
+            album.album(&uploaded.url)
+        },
+        Err(_) if reports.iter().all(|report| report.status != Status::Ok) => {
+            album.failed_because("No files were uploaded")
+        },
+        Err(err) => album.failed(err),
+    };
+    (reports, album)
+}
+
+/// Path or URL to upload, or the report of one that can't be
+#[derive(Clone)]
+enum Input {
+    Uri(String),
+    Done(Report),
+}
+
+/// Inputs with directories replaced by the files in them if they are walked,
+/// or else by a report of skipping them
+fn expand_inputs(inputs: Vec<String>, walk_args: &WalkArgs) -> Vec<Input> {
+    let walker = walk_args.recursive.then(|| Walker::new(walk_args));
+    let mut expanded = Vec::new();
+    for input in inputs {
+        match (&walker, Path::new(&input).is_dir()) {
+            (Some(walker), true) => {
+                expanded.extend(walker.walk(Path::new(&input)).into_iter().map(|walked| {
+                    match walked {
+                        Ok(path) => Input::Uri(path.to_string_lossy().to_string()),
+                        Err((path, err)) => Input::Done(
+                            Report::new("Reading", path.to_string_lossy(), Instant::now())
+                                .failed(err.into()),
+                        ),
+                    }
+                }))
+            },
+            (None, true) => expanded.push(Input::Done(
+                Report::new("Uploading", input, Instant::now())
+                    .failed_because("Is a directory. Use --recursive to upload the files in it."),
+            )),
+            (_, false) => expanded.push(Input::Uri(input)),
+        }
     }
+    expanded
 }
 
-async fn delete_file(delete_args: Delete) -> Result<(), Box<dyn Error>> {
-    let res = file::delete(
-        delete_args
-            .user_hash
-            .unwrap_or(user_hash_from_env().as_deref().unwrap_or("").to_string()),
-        delete_args
-            .files
+/// Inputs that are paths of existing files
+fn existing_files(inputs: &[Input]) -> Vec<String> {
+    inputs
+        .iter()
+        .filter_map(|input| match input {
+            Input::Uri(uri) if Path::new(uri).is_file() => Some(uri.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Run `tasks`, at most `concurrency` at a time, printing each report as it's done.
+/// Returns the reports in the order of the tasks.
+async fn run_reports<F>(
+    tasks: impl IntoIterator<Item = F>,
+    concurrency: usize,
+    output: OutputFormat,
+    bars: &UploadBars,
+) -> Vec<Report>
+where
+    F: Future<Output = Report>,
+{
+    if let Some(header) = output.header() {
+        bars.println(&header);
+    }
+    let mut reports: Vec<_> = stream::iter(
+        tasks
             .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
+            .enumerate()
+            .map(|(index, task)| async move { (index, task.await) }),
+    )
+    .buffer_unordered(concurrency)
+    .inspect(|(_, report)| {
+        if let Some(line) = output.line(report) {
+            bars.println(&line);
+        }
+    })
+    .collect()
+    .await;
+    reports.sort_by_key(|(index, _)| *index);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+/// Upload stdin if `uri` is `-`, a file if it's an existing path, or else the URL
+async fn upload_uri(
+    uri: String,
+    name: Option<String>,
+    client: &CatboxClient,
+    bars: &UploadBars,
+) -> Report {
+    if uri == STDIN {
+        upload_stdin(name, None, client).await
+    } else if Path::new(&uri).exists() {
+        upload_file(uri, client, bars).await
+    } else if Url::parse(&uri).is_ok() {
+        upload_url(uri, client).await
+    } else {
+        invalid_uri(uri)
+    }
+}
+
+fn invalid_uri(uri: String) -> Report {
+    Report::new("Uploading", uri, Instant::now()).failed_because("Not a file or valid URL")
+}
+
+/// Warn if sniffing is enabled and the file's contents don't match its extension
+async fn warn_mismatch(file_path: &str, client: &CatboxClient, bars: &UploadBars) {
+    if client.content_sniffing() == ContentSniffing::Off {
+        return;
+    }
+    if let Ok(Some(content_type)) = ContentType::detect_file(file_path).await {
+        if !content_type.matches(file_path) {
+            bars.eprintln(&format!(
+                "Warning: {} looks like {} but doesn't have a .{} extension",
+                file_path, content_type.mime, content_type.extension
+            ));
+        }
+    }
+}
+
+fn file_size(file_path: &str) -> Option<u64> {
+    std::fs::metadata(file_path)
+        .ok()
+        .map(|metadata| metadata.len())
+}
+
+async fn upload_file(file: String, client: &CatboxClient, bars: &UploadBars) -> Report {
+    warn_mismatch(&file, client, bars).await;
+    let bar = bars.file(&file);
+    let started = Instant::now();
+    let res = client
+        .upload_file_with_progress(file.clone(), bar.callback())
+        .await;
+    let report = Report::new("Uploading", &file, started).size(file_size(&file));
+    match res {
+        Ok(url) => report.file(&url),
+        Err(err) => report.failed(err),
+    }
+}
+
+async fn upload_url(url: String, client: &CatboxClient) -> Report {
+    let started = Instant::now();
+    let res = client.upload_url(&url).await;
+    let report = Report::new("Uploading", url, started);
+    match res {
+        Ok(url) => report.file(&url),
+        Err(err) => report.failed(err),
+    }
+}
+
+async fn upload_to_litter(
+    file_path: String,
+    name: Option<String>,
+    time: LitterExpiry,
+    client: &CatboxClient,
+    bars: &UploadBars,
+) -> Report {
+    if file_path == STDIN {
+        return upload_stdin(name, Some(time), client).await;
+    }
+    if !Path::new(&file_path).exists() {
+        return Report::new("Uploading", file_path, Instant::now()).failed_because("Not a file");
+    }
+    warn_mismatch(&file_path, client, bars).await;
+    let bar = bars.file(&file_path);
+    let started = Instant::now();
+    let res = client
+        .upload_litter_with_progress(file_path.clone(), time, bar.callback())
+        .await;
+    let report = Report::new("Uploading", &file_path, started).size(file_size(&file_path));
+    match res {
+        Ok(url) => report.file(&url),
+        Err(err) => report.failed(err),
+    }
+}
+
+/// Only one input can be read from stdin, and only it can be named
+fn check_stdin(files: &[String], name: &Option<String>) -> Result<(), ExitError> {
+    match files.iter().filter(|file| *file == STDIN).count() {
+        0 if name.is_some() => Err(ExitError::new(
+            Exit::Usage,
+            "--name is only used for uploading stdin (-)",
+        )),
+        0 | 1 => Ok(()),
+        _ => Err(ExitError::new(
+            Exit::Usage,
+            "stdin (-) can only be uploaded once",
+        )),
+    }
+}
+
+/// Name of the contents of stdin: `name` or "stdin", with the extension
+/// detected from the contents if it has none
+fn stdin_name(name: Option<String>, bytes: &[u8]) -> String {
+    let name = name.unwrap_or_else(|| "stdin".to_string());
+    match (Path::new(&name).extension(), ContentType::detect(bytes)) {
+        (None, Some(content_type)) => content_type.fix_name(&name),
+        _ => name,
+    }
+}
+
+/// Upload all of stdin to Litterbox for `time`, or else to Catbox.
+/// Only the start is read ahead to name it, the rest is sent as it's read.
+async fn upload_stdin(
+    name: Option<String>,
+    time: Option<LitterExpiry>,
+    client: &CatboxClient,
+) -> Report {
+    let started = Instant::now();
+    let mut stdin = tokio::io::stdin();
+    let mut start = Vec::with_capacity(SNIFF_LENGTH);
+    if let Err(err) = (&mut stdin)
+        .take(SNIFF_LENGTH as u64)
+        .read_to_end(&mut start)
+        .await
+    {
+        return Report::new("Reading", STDIN, started).failed(err.into());
+    }
+    let name = stdin_name(name, &start);
+    let size = Arc::new(AtomicU64::new(0));
+    let sent = size.clone();
+    let stream = ReaderStream::new(io::Cursor::new(start).chain(stdin)).inspect_ok(move |chunk| {
+        sent.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    });
+    let res = match time {
+        Some(time) => client.upload_litter_stream(stream, name, time).await,
+        None => client.upload_stream(stream, name).await,
+    };
+    let report = Report::new("Uploading", STDIN, started).size(Some(size.load(Ordering::Relaxed)));
+    match res {
+        Ok(url) => report.file(&url),
+        Err(err) => report.failed(err),
+    }
+}
+
+/// Print the report of a command making a single request.
+/// A failure is printed as an error when printing text.
+fn print_report(report: Report, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let exit = report.exit();
+    match (output, report.status) {
+        (OutputFormat::Text, Status::Failed) => Err(ExitError::new(exit, report.text()).into()),
+        (_, Status::Failed) => {
+            output.print(&[report]);
+            Err(ExitError::silent(exit).into())
+        },
+        _ => {
+            output.print(&[report]);
+            Ok(())
+        },
+    }
+}
+
+async fn delete_file(delete_args: Delete) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&delete_args.client_args)?;
+    let user_hash = require_user_hash(delete_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &delete_args.client_args, &profile);
+    let output = output_format(&delete_args.client_args, &profile);
+    let files = file_ids(&client, &delete_args.files)?;
+    let input = files
+        .iter()
+        .map(FileId::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let started = Instant::now();
+    let res = client.delete_files(files).await;
+    let report = Report::new("Deleting", input, started);
+    print_report(
+        match res {
+            Ok(res) => report.message(res),
+            Err(err) => report.failed(err),
+        },
+        output,
     )
-    .await?;
-    println!("{}", res);
-    Ok(())
 }
 
 async fn litter(litter_args: Litter) -> Result<(), Box<dyn Error>> {
-    let (files, rest): (Vec<_>, _) = litter_args
-        .files
-        .into_iter()
-        .partition(|path| Path::new(&path).exists());
-    let print_res = |res| async move { println!("{}", res) };
-    tokio::join!(
-        rest.into_iter()
-            .map(|uri| invalid_uri(uri))
-            .collect::<FuturesUnordered<_>>()
-            .for_each_concurrent(10, print_res),
-        files
-            .into_iter()
-            .map(|file| upload_to_litter(file, litter_args.time.unwrap_or(1)))
-            .collect::<FuturesUnordered<_>>()
-            .for_each_concurrent(10, print_res),
-    );
-    Ok(())
+    check_stdin(&litter_args.files, &litter_args.name)?;
+    let inputs = expand_inputs(litter_args.files, &litter_args.walk_args);
+    let profile = config::load_profile(&litter_args.client_args)?;
+    let time = litter_args
+        .time
+        .or(profile.litter_expiry)
+        .unwrap_or_default();
+    let client = client(None, &litter_args.client_args, &profile);
+    let concurrency = concurrency(&litter_args.client_args, &profile);
+    let output = output_format(&litter_args.client_args, &profile);
+    let bars = UploadBars::new(&existing_files(&inputs));
+
+    let reports = run_reports(
+        inputs.into_iter().map(|input| match input {
+            Input::Uri(file) => Either::Left(upload_to_litter(
+                file,
+                litter_args.name.clone(),
+                time,
+                &client,
+                &bars,
+            )),
+            Input::Done(report) => Either::Right(future::ready(report)),
+        }),
+        concurrency,
+        output,
+        &bars,
+    )
+    .await;
+    bars.finish();
+    if let Some(footer) = output.footer(&reports) {
+        println!("{}", footer);
+    }
+    Ok(exit::check_batch(&reports)?)
 }
 
-async fn create_album(album_create_args: AlbumCreate) -> Result<(), Box<dyn Error>> {
-    let res = album::create(
-        album_create_args.title,
-        album_create_args.description.unwrap_or_default(),
-        album_create_args.user_hash.or(user_hash_from_env()),
-        album_create_args
-            .files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
+async fn show_album(album_show_args: AlbumShow) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&album_show_args.client_args)?;
+    let client = client(None, &album_show_args.client_args, &profile);
+    let output = output_format(&album_show_args.client_args, &profile);
+    let short = album_short(&client, &album_show_args.short)?;
+    let input = short.to_string();
+    let started = Instant::now();
+    match client.get_album(short).await {
+        Ok(album) => {
+            println!("{}", output.album(&album));
+            Ok(())
+        },
+        Err(err) => print_report(
+            Report::new("Reading album", input, started).failed(err),
+            output,
+        ),
+    }
+}
+
+/// File to download, or the report of an input that can't be
+enum Planned {
+    File {
+        input: String,
+        url: FileUrl,
+        path: PathBuf,
+    },
+    Done(Report),
+}
+
+async fn download(download_args: Download) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&download_args.client_args)?;
+    let client = client(None, &download_args.client_args, &profile);
+    let concurrency = concurrency(&download_args.client_args, &profile);
+    let output = output_format(&download_args.client_args, &profile);
+    let dir = download_args.dir;
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("Couldn't create {}: {}", dir.display(), err))?;
+
+    let mut targets = Targets::new(&dir, download_args.on_conflict);
+    let planned = plan_downloads(download_args.inputs, &client, &mut targets).await;
+
+    // Sizes aren't known before downloading, so there's no bar for the total
+    let bars = UploadBars::new(&[]);
+    let reports = run_reports(
+        planned.into_iter().map(|planned| match planned {
+            Planned::File { input, url, path } => {
+                Either::Left(download_file(input, url, path, &client, &bars))
+            },
+            Planned::Done(report) => Either::Right(future::ready(report)),
+        }),
+        concurrency,
+        output,
+        &bars,
     )
-    .await?;
-    println!("{}", res);
-    Ok(())
+    .await;
+    bars.finish();
+    if let Some(footer) = output.footer(&reports) {
+        println!("{}", footer);
+    }
+    Ok(exit::check_batch(&reports)?)
+}
+
+/// Files to download for the inputs, with albums replaced by their files,
+/// or reports of inputs that aren't downloaded
+async fn plan_downloads(
+    inputs: Vec<String>,
+    client: &CatboxClient,
+    targets: &mut Targets,
+) -> Vec<Planned> {
+    let mut listed = HashSet::new();
+    let mut plan = |input: String, url: FileUrl| {
+        if !listed.insert(url.clone()) {
+            return Planned::Done(Report::skipped(input, "Listed more than once"));
+        }
+        match targets.path(&url) {
+            Ok(path) => Planned::File { input, url, path },
+            Err(reason) => Planned::Done(Report::skipped(input, reason)),
+        }
+    };
+    let mut planned = Vec::new();
+    for input in inputs {
+        match Source::parse(&input, client.album_url(), client.files_url()) {
+            Ok(Source::File(url)) => planned.push(plan(input, url)),
+            Ok(Source::Album(short)) => {
+                let started = Instant::now();
+                match client.get_album(short).await {
+                    Ok(album) => planned.extend(
+                        album
+                            .files
+                            .into_iter()
+                            .map(|url| plan(url.to_string(), url)),
+                    ),
+                    Err(err) => planned.push(Planned::Done(
+                        Report::new("Reading album", input, started).failed(err),
+                    )),
+                }
+            },
+            Err(err) => planned.push(Planned::Done(
+                Report::new("Downloading", input, Instant::now()).failed_because(err),
+            )),
+        }
+    }
+    planned
+}
+
+async fn download_file(
+    input: String,
+    url: FileUrl,
+    path: PathBuf,
+    client: &CatboxClient,
+    bars: &UploadBars,
+) -> Report {
+    let started = Instant::now();
+    let bar = bars.file(&path.to_string_lossy());
+    let res = client
+        .download_to_file_with_progress(&url, &path, bar.callback())
+        .await;
+    let report = Report::new("Downloading", input, started).file(&url);
+    match res {
+        Ok(size) => report.size(Some(size)).path(&path),
+        Err(err) => report.failed(err),
+    }
+}
+
+async fn create_album(album_create_args: AlbumCreate) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&album_create_args.client_args)?;
+    let client = client(
+        album_create_args.user_hash,
+        &album_create_args.client_args,
+        &profile,
+    );
+    let output = output_format(&album_create_args.client_args, &profile);
+    let files = file_ids(&client, &album_create_args.files)?;
+    let title = album_create_args.title.clone();
+    let started = Instant::now();
+    let res = client
+        .create_album(
+            album_create_args.title,
+            album_create_args.description.unwrap_or_default(),
+            files,
+        )
+        .await;
+    album_report("Creating album", title, started, res, output)
 }
 
 async fn delete_album(album_delete_args: AlbumDelete) -> Result<(), Box<dyn Error>> {
-    let res = album::delete(
-        album_url_to_short(&album_delete_args.short),
-        album_delete_args
-            .user_hash
-            .unwrap_or(user_hash_from_env().unwrap_or(String::new())),
+    let profile = config::load_profile(&album_delete_args.client_args)?;
+    let user_hash = require_user_hash(album_delete_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &album_delete_args.client_args, &profile);
+    let output = output_format(&album_delete_args.client_args, &profile);
+    let short = album_short(&client, &album_delete_args.short)?;
+    let input = short.to_string();
+    let started = Instant::now();
+    let res = client.delete_album(short).await;
+    let report = Report::new("Deleting album", input, started);
+    print_report(
+        match res {
+            Ok(res) => report.message(res),
+            Err(err) => report.failed(err),
+        },
+        output,
     )
-    .await?;
-    println!("{}", res);
-    Ok(())
 }
 
 async fn edit_album(album_edit_args: AlbumEdit) -> Result<(), Box<dyn Error>> {
-    let res = album::edit(
-        album_url_to_short(&album_edit_args.short),
-        album_edit_args.title,
-        album_edit_args.description.unwrap_or_default(),
-        album_edit_args
-            .user_hash
-            .unwrap_or(user_hash_from_env().unwrap_or(String::new())),
-        album_edit_args
-            .files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
-    )
-    .await?;
-    println!("{}", res);
-    Ok(())
+    let profile = config::load_profile(&album_edit_args.client_args)?;
+    let user_hash = require_user_hash(album_edit_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &album_edit_args.client_args, &profile);
+    let output = output_format(&album_edit_args.client_args, &profile);
+    let short = album_short(&client, &album_edit_args.short)?;
+    let files = file_ids(&client, &album_edit_args.files)?;
+    let input = short.to_string();
+    let started = Instant::now();
+    let res = client
+        .edit_album(
+            short,
+            album_edit_args.title,
+            album_edit_args.description.unwrap_or_default(),
+            files,
+        )
+        .await;
+    album_report("Editing album", input, started, res, output)
+}
+
+async fn update_album(album_update_args: AlbumUpdate) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&album_update_args.client_args)?;
+    let user_hash = require_user_hash(album_update_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &album_update_args.client_args, &profile);
+    let output = output_format(&album_update_args.client_args, &profile);
+    let short = album_short(&client, &album_update_args.short)?;
+    let input = short.to_string();
+    let changes = AlbumChanges {
+        title: album_update_args.title,
+        description: album_update_args.description,
+        add: file_ids(&client, &album_update_args.add)?,
+        remove: file_ids(&client, &album_update_args.remove)?,
+    };
+    let started = Instant::now();
+    let res = client
+        .update_album(short, changes)
+        .await
+        .map(|album| album.url);
+    album_report("Updating album", input, started, res, output)
 }
 
 async fn add_to_album(album_add_args: AlbumAdd) -> Result<(), Box<dyn Error>> {
-    let res = album::add_files(
-        album_url_to_short(&album_add_args.short),
-        album_add_args
-            .user_hash
-            .unwrap_or(user_hash_from_env().unwrap_or(String::new())),
-        album_add_args
-            .files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
-    )
-    .await?;
-    println!("{}", res);
-    Ok(())
+    let profile = config::load_profile(&album_add_args.client_args)?;
+    let user_hash = require_user_hash(album_add_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &album_add_args.client_args, &profile);
+    let output = output_format(&album_add_args.client_args, &profile);
+    let short = album_short(&client, &album_add_args.short)?;
+    let files = file_ids(&client, &album_add_args.files)?;
+    let input = short.to_string();
+    let started = Instant::now();
+    let res = client.add_to_album(short, files).await;
+    album_report("Adding files to album", input, started, res, output)
 }
 
 async fn remove_from_album(album_remove_args: AlbumRemove) -> Result<(), Box<dyn Error>> {
-    let res = album::remove_files(
-        album_url_to_short(&album_remove_args.short),
-        album_remove_args
-            .user_hash
-            .unwrap_or(user_hash_from_env().unwrap_or(String::new())),
-        album_remove_args
-            .files
+    let profile = config::load_profile(&album_remove_args.client_args)?;
+    let user_hash = require_user_hash(album_remove_args.user_hash, &profile)?;
+    let client = client(Some(user_hash), &album_remove_args.client_args, &profile);
+    let output = output_format(&album_remove_args.client_args, &profile);
+    let short = album_short(&client, &album_remove_args.short)?;
+    let files = file_ids(&client, &album_remove_args.files)?;
+    let input = short.to_string();
+    let started = Instant::now();
+    let res = client.remove_from_album(short, files).await;
+    album_report("Removing files from album", input, started, res, output)
+}
+
+async fn sync_album(album_sync_args: AlbumSync) -> Result<(), Box<dyn Error>> {
+    let profile = config::load_profile(&album_sync_args.client_args)?;
+    let user_hash = match album_sync_args.dry_run {
+        true => user_hash(album_sync_args.user_hash, &profile),
+        false => Some(require_user_hash(album_sync_args.user_hash, &profile)?),
+    };
+    let client = client(user_hash, &album_sync_args.client_args, &profile);
+    let concurrency = concurrency(&album_sync_args.client_args, &profile);
+    let output = output_format(&album_sync_args.client_args, &profile);
+    let dir = album_sync_args.dir;
+    let short = album_short(&client, &album_sync_args.short)?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()).into());
+    }
+    let state_path = album_sync_args
+        .state
+        .unwrap_or_else(|| dir.join(sync::STATE_FILE));
+    let mut state = State::load(&state_path)?;
+    state.check_album(&short, &state_path)?;
+
+    let walker = Walker::new(&WalkArgs {
+        recursive: true,
+        include: album_sync_args.include,
+        exclude: album_sync_args.exclude,
+        gitignore: album_sync_args.gitignore,
+        hidden: album_sync_args.hidden,
+        symlinks: Symlinks::Skip,
+    });
+    let (local, unreadable) = sync::local_files(&dir, &walker, &state_path).await;
+    let album = client.get_album(short.clone()).await?;
+    let delete_missing = album_sync_args.delete_missing;
+    let plan = Plan::new(
+        &local,
+        &state,
+        &album,
+        album_sync_args.remove_missing || delete_missing,
+    );
+    let unreadable = unreadable.into_iter().map(|(path, err)| {
+        Report::new("Reading", path.to_string_lossy(), Instant::now()).failed(err.into())
+    });
+    if plan.is_empty() {
+        eprintln!("Album {} is up to date", short);
+    }
+
+    if album_sync_args.dry_run {
+        let reports: Vec<_> = unreadable
+            .chain(sync_plan_reports(&plan, delete_missing))
+            .collect();
+        output.print(&reports);
+        return Ok(exit::check_batch(&reports)?);
+    }
+
+    let uploads: Vec<_> = plan
+        .upload
+        .iter()
+        .map(|file| file.path.to_string_lossy().to_string())
+        .collect();
+    let bars = UploadBars::new(&uploads);
+    let unreadable: Vec<_> = unreadable.collect();
+    let skipped = unreadable.len();
+    let mut reports = run_reports(
+        unreadable
             .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
+            .map(|report| Either::Right(future::ready(report)))
+            .chain(
+                uploads
+                    .into_iter()
+                    .map(|file| Either::Left(upload_file(file, &client, &bars))),
+            ),
+        concurrency,
+        output,
+        &bars,
+    )
+    .await;
+    bars.finish();
+
+    let mut add = Vec::new();
+    for (file, report) in plan.upload.iter().zip(&reports[skipped..]) {
+        if let Some(id) = report.id.as_deref().filter(|_| report.status == Status::Ok) {
+            state.files.insert(
+                file.hash.clone(),
+                Synced {
+                    id: id.to_string(),
+                    path: file.relative.clone(),
+                },
+            );
+            add.extend(id.parse::<FileId>());
+        }
+    }
+    // Saved before changing the album, so the uploads aren't repeated if that fails
+    state.save(&state_path)?;
+    add.extend(plan.add.iter().map(|(_, id)| id.clone()));
+
+    let mut print = |report: Report| {
+        if let Some(line) = output.line(&report) {
+            println!("{}", line);
+        }
+        reports.push(report);
+    };
+    if !add.is_empty() {
+        let started = Instant::now();
+        let res = client.add_to_album(short.clone(), add).await;
+        let report = Report::new("Adding files to album", short.to_string(), started);
+        print(match res {
+            Ok(url) => report.album(&url),
+            Err(err) => report.failed(err),
+        });
+    }
+    let remove: Vec<FileId> = plan
+        .remove
+        .iter()
+        .filter_map(|(_, synced)| synced.id.parse().ok())
+        .collect();
+    if !remove.is_empty() {
+        let started = Instant::now();
+        let res = client
+            .remove_from_album(short.clone(), remove.clone())
+            .await;
+        let report = Report::new("Removing files from album", short.to_string(), started);
+        let removed = res.is_ok();
+        print(match res {
+            Ok(url) => report.album(&url),
+            Err(err) => report.failed(err),
+        });
+        if removed && delete_missing {
+            let input = remove.iter().map(FileId::to_string).collect::<Vec<_>>();
+            let started = Instant::now();
+            let res = client.delete_files(remove).await;
+            let report = Report::new("Deleting", input.join(" "), started);
+            let deleted = res.is_ok();
+            print(match res {
+                Ok(res) => report.message(res),
+                Err(err) => report.failed(err),
+            });
+            // Removed files are kept in the state so they're added back if they return
+            if deleted {
+                for (hash, _) in &plan.remove {
+                    state.files.remove(hash);
+                }
+            }
+        }
+    }
+
+    for file in &local {
+        if let Some(synced) = state.files.get_mut(&file.hash) {
+            synced.path = file.relative.clone();
+        }
+    }
+    state.save(&state_path)?;
+    if let Some(footer) = output.footer(&reports) {
+        println!("{}", footer);
+    }
+    Ok(exit::check_batch(&reports)?)
+}
+
+/// Reports of what syncing would do, for `--dry-run`
+fn sync_plan_reports(plan: &Plan, delete_missing: bool) -> Vec<Report> {
+    let started = Instant::now();
+    let upload = plan.upload.iter().map(|file| {
+        Report::new("Uploading", &file.relative, started)
+            .message(format!("Would upload {}", file.relative))
+    });
+    let add = plan.add.iter().map(|(file, id)| {
+        Report::new("Adding", &file.relative, started)
+            .message(format!("Would add {} ({}) to the album", file.relative, id))
+    });
+    let remove = plan.remove.iter().map(|(_, synced)| {
+        let message = match delete_missing {
+            true => format!(
+                "Would remove {} ({}) from the album and delete it",
+                synced.path, synced.id
+            ),
+            false => format!(
+                "Would remove {} ({}) from the album",
+                synced.path, synced.id
+            ),
+        };
+        Report::new("Removing", &synced.path, started).message(message)
+    });
+    upload.chain(add).chain(remove).collect()
+}
+
+fn album_report(
+    action: &'static str,
+    input: String,
+    started: Instant,
+    res: Result<AlbumUrl, CatboxError>,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let report = Report::new(action, input, started);
+    print_report(
+        match res {
+            Ok(url) => report.album(&url),
+            Err(err) => report.failed(err),
+        },
+        output,
     )
-    .await?;
-    println!("{}", res);
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
+    use crate::config::Key;
+    use catbox::FileId;
+    use std::{ffi::OsString, io::Write};
     use tempfile::Builder;
 
     static FILE_URL: &str =
         "https://file-examples.com/wp-content/uploads/2017/10/file_example_JPG_100kB.jpg";
 
+    /// Parse `args` with a config file that doesn't exist, so that the
+    /// developer's own config and `CATBOX_CONFIG` aren't read
+    fn parse_args<I, T>(args: I) -> CatboxArgs
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        CatboxArgs::parse_from(without_config(args))
+    }
+
+    fn try_parse_args<I, T>(args: I) -> Result<CatboxArgs, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        CatboxArgs::try_parse_from(without_config(args))
+    }
+
+    fn without_config<I, T>(args: I) -> Vec<OsString>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let missing = env::temp_dir()
+            .join("catbox-tests-without-config")
+            .join("config.toml");
+        args.splice(1..1, ["--config".into(), missing.into()]);
+        args
+    }
+
     #[tokio::test]
     async fn upload_file() -> Result<(), Box<dyn Error>> {
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "upload",
             "--user",
@@ -268,7 +1284,7 @@ mod tests {
 
     #[tokio::test]
     async fn upload_url() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec!["catbox", "upload", "--user", "123345", FILE_URL]);
+        let args = parse_args(vec!["catbox", "upload", "--user", "123345", FILE_URL]);
 
         if let CatboxCommand::Upload(upload_args) = args.command {
             upload(upload_args).await?;
@@ -281,7 +1297,7 @@ mod tests {
 
     #[tokio::test]
     async fn nonexistant() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "upload",
             "--user",
@@ -290,7 +1306,8 @@ mod tests {
         ]);
 
         if let CatboxCommand::Upload(upload_args) = args.command {
-            upload(upload_args).await?;
+            let err = upload(upload_args).await.unwrap_err();
+            assert_eq!(exit::exit_code(err.as_ref()), Exit::Failure);
         } else {
             panic!("Invalid subcommand");
         }
@@ -303,7 +1320,7 @@ mod tests {
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "upload",
             "--user",
@@ -315,7 +1332,8 @@ mod tests {
         ]);
 
         if let CatboxCommand::Upload(upload_args) = args.command {
-            upload(upload_args).await?;
+            let err = upload(upload_args).await.unwrap_err();
+            assert_eq!(exit::exit_code(err.as_ref()), Exit::Partial);
         } else {
             panic!("Invalid subcommand");
         }
@@ -325,7 +1343,7 @@ mod tests {
 
     #[tokio::test]
     async fn delete_files() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "delete",
             "--user",
@@ -345,7 +1363,7 @@ mod tests {
 
     #[tokio::test]
     async fn album_create() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "album",
             "create",
@@ -368,9 +1386,77 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn album_show() -> Result<(), Box<dyn Error>> {
+        let args = parse_args(vec![
+            "catbox",
+            "album",
+            "show",
+            "https://catbox.moe/c/123asd",
+        ]);
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let album = CatboxClient::builder()
+            .build()
+            .get_album("123asd".parse()?)
+            .await?;
+        assert_eq!(
+            OutputFormat::Text.album(&album),
+            "My album\nA description\n\nhttps://files.catbox.moe/file.png"
+        );
+        assert_eq!(
+            OutputFormat::Tsv.album(&album),
+            "url\tid\nhttps://files.catbox.moe/file.png\tfile.png"
+        );
+        let json: serde_json::Value = serde_json::from_str(&OutputFormat::Json.album(&album))?;
+        assert_eq!(json["id"], "123asd");
+        assert_eq!(json["files"][0], "https://files.catbox.moe/file.png");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn album_update() -> Result<(), Box<dyn Error>> {
+        let args = parse_args(vec![
+            "catbox",
+            "album",
+            "update",
+            "--user",
+            "123345",
+            "--short",
+            "123asd",
+            "--title",
+            "New title",
+            "--add",
+            "file.png",
+            "another.jpg",
+        ]);
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        // At least one change is needed
+        assert!(
+            try_parse_args(vec![
+                "catbox", "album", "update", "--user", "123345", "--short", "123asd",
+            ])
+            .is_err()
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn album_add() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox", "album", "add", "--user", "123345", "--short", "123asd", "file.png",
         ]);
 
@@ -385,7 +1471,7 @@ mod tests {
 
     #[tokio::test]
     async fn album_remove() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox", "album", "remove", "--user", "123345", "--short", "123asd", "file.png",
         ]);
 
@@ -400,7 +1486,7 @@ mod tests {
 
     #[tokio::test]
     async fn album_delete() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox", "album", "delete", "--user", "123345", "asd123",
         ]);
 
@@ -415,7 +1501,7 @@ mod tests {
 
     #[tokio::test]
     async fn album_edit() -> Result<(), Box<dyn Error>> {
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "album",
             "edit",
@@ -445,11 +1531,11 @@ mod tests {
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
-        let args = CatboxArgs::parse_from(vec![
+        let args = parse_args(vec![
             "catbox",
             "litter",
             "--time",
-            "1",
+            "3d",
             file.path().to_str().unwrap(),
         ]);
 
@@ -462,6 +1548,719 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn ids_from_urls() {
+        let args = parse_args(vec![
+            "catbox",
+            "delete",
+            "https://files.catbox.moe/abc123.png",
+            "www.files.example.com/uploads/def456.jpg/",
+            "ghi789.gif",
+        ]);
+
+        if let CatboxCommand::Delete(delete_args) = args.command {
+            let client = CatboxClient::builder()
+                .files_url("https://files.example.com/uploads/")
+                .build();
+            assert_eq!(
+                file_ids(&client, &delete_args.files)
+                    .unwrap()
+                    .iter()
+                    .map(FileId::as_str)
+                    .collect::<Vec<_>>(),
+                vec!["abc123.png", "def456.jpg", "ghi789.gif"]
+            );
+            let err = file_ids(&CatboxClient::default(), &delete_args.files).unwrap_err();
+            assert_eq!(err.exit, Exit::Usage);
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let client = CatboxClient::builder()
+            .album_url("https://example.com/albums/")
+            .build();
+        assert_eq!(
+            album_short(&client, "https://example.com/albums/xyz789")
+                .unwrap()
+                .as_str(),
+            "xyz789"
+        );
+        assert!(album_short(&client, "not an album").is_err());
+        // An album is never taken for a file
+        assert!(file_ids(&client, &["https://catbox.moe/c/xyz789".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn blocked_files() {
+        let file = Builder::new().suffix(".exe").tempfile().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let args = parse_args(["catbox", "upload", path]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            let client = client(None, &upload_args.client_args, &Profile::default());
+            let bars = UploadBars::new(&[]);
+            let report = super::upload_file(path.to_string(), &client, &bars).await;
+            assert_eq!(report.status, Status::Failed);
+            assert_eq!(
+                report.error.as_deref(),
+                Some("Files with the .exe extension are not allowed")
+            );
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let args = parse_args(["catbox", "upload", "--no-limits", path]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            let client = client(None, &upload_args.client_args, &Profile::default());
+            let bars = UploadBars::new(&[]);
+            let report = super::upload_file(path.to_string(), &client, &bars).await;
+            assert!(report.url.unwrap().ends_with(".exe"));
+        } else {
+            panic!("Invalid subcommand");
+        }
+    }
+
+    #[tokio::test]
+    async fn fix_extension() {
+        let mut file = Builder::new().suffix(".tmp").tempfile().unwrap();
+        file.write_all(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let args = parse_args(["catbox", "upload", "--fix-extension", path]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            let client = client(None, &upload_args.client_args, &Profile::default());
+            let bars = UploadBars::new(&[]);
+            let report = super::upload_file(path.to_string(), &client, &bars).await;
+            assert!(report.url.unwrap().ends_with(".png"));
+        } else {
+            panic!("Invalid subcommand");
+        }
+    }
+
+    #[test]
+    fn history_export() {
+        let mut upload = Entry::new(catbox::history::Action::Upload);
+        upload.source = Some("/tmp/a, b.png".to_string());
+        upload.url = Some("https://files.catbox.moe/abc123.png".to_string());
+        upload.user_hash = Some("secret".to_string());
+        let mut album = Entry::new(catbox::history::Action::AlbumCreate);
+        album.title = Some("Cats | Dogs".to_string());
+        album.album = Some("xyz789".to_string());
+        let entries = vec![(1, upload), (2, album)];
+
+        let csv = history::export(&entries, args::ExportFormat::Csv).unwrap();
+        assert!(csv.starts_with("number,time,action"));
+        assert!(csv.contains(",\"/tmp/a, b.png\","));
+        let markdown = history::export(&entries, args::ExportFormat::Markdown).unwrap();
+        assert!(markdown.contains("Cats \\| Dogs"));
+        let json = history::export(&entries, args::ExportFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Entry>>(&json).unwrap().len(), 2);
+        assert!(!csv.contains("secret") && !json.contains("secret"));
+        assert!(!history::show(1, &entries[0].1).contains("secret"));
+
+        assert_eq!(history::find(&entries, "2").unwrap().0, 2);
+        assert_eq!(history::find(&entries, "abc123.png").unwrap().0, 1);
+        assert_eq!(history::find(&entries, "xyz789").unwrap().0, 2);
+        assert!(history::find(&entries, "3").is_none());
+    }
+
+    #[test]
+    fn history_filters() {
+        let args = parse_args([
+            "catbox",
+            "history",
+            "list",
+            "--service",
+            "litterbox",
+            "--since",
+            "2024-01-01",
+            "--until",
+            "2024-01-31",
+        ]);
+        if let CatboxCommand::History(history_args) = args.command {
+            if let HistoryCommand::List(list_args) = history_args.history_command {
+                let filter = history::filter(list_args.filter, None);
+                assert!(filter.since < filter.until);
+                let mut entry = Entry::new(catbox::history::Action::LitterUpload);
+                assert!(!filter.matches(&entry));
+                entry.time = "2024-01-31T11:00:00Z".parse().unwrap();
+                assert!(filter.matches(&entry));
+                return;
+            }
+        }
+        panic!("Invalid subcommand");
+    }
+
+    #[test]
+    fn config_profiles() {
+        let config: config::Config = toml::from_str(
+            r#"
+            default_profile = "work"
+
+            [profiles.work]
+            user_hash = "hash"
+            litter_expiry = "3d"
+            timeout = 30
+
+            [profiles.home]
+            retries = 2
+            "#,
+        )
+        .unwrap();
+        let work = config.profile(None).unwrap();
+        assert_eq!(work.user_hash.as_deref(), Some("hash"));
+        assert_eq!(work.litter_expiry, Some(LitterExpiry::ThreeDays));
+        assert_eq!(config.profile(Some("home")).unwrap().retries, Some(2));
+        assert!(config.profile(Some("missing")).is_err());
+
+        assert_eq!(
+            config::Config::default().profile(None).unwrap(),
+            Profile::default()
+        );
+        assert!(toml::from_str::<config::Config>("[profiles.a]\nunknown = 1").is_err());
+        assert!(toml::from_str::<config::Config>("[profiles.a]\nlitter_expiry = \"2d\"").is_err());
+    }
+
+    #[test]
+    fn config_precedence() {
+        let profile = Profile {
+            concurrency: Some(3),
+            ..Profile::default()
+        };
+        let args = parse_args(["catbox", "upload", "file"]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(concurrency(&upload_args.client_args, &profile), 3);
+            assert_eq!(
+                concurrency(&upload_args.client_args, &Profile::default()),
+                DEFAULT_CONCURRENCY
+            );
+        }
+        let args = parse_args(["catbox", "upload", "--concurrency", "5", "file"]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(concurrency(&upload_args.client_args, &profile), 5);
+        }
+    }
+
+    #[test]
+    fn env_settings() {
+        env::set_var("CATBOX_TEST_SETTING", "4");
+        assert_eq!(env_setting::<u32>("CATBOX_TEST_SETTING"), Some(4));
+        env::set_var("CATBOX_TEST_SETTING", "four");
+        assert_eq!(env_setting::<u32>("CATBOX_TEST_SETTING"), None);
+        env::remove_var("CATBOX_TEST_SETTING");
+        assert_eq!(env_setting::<u32>("CATBOX_TEST_SETTING"), None);
+    }
+
+    #[test]
+    fn config_keys() {
+        let mut profile = Profile::default();
+        profile.set(Key::LitterExpiry, Some("3d")).unwrap();
+        profile.set(Key::Retries, Some("2")).unwrap();
+        assert_eq!(profile.get(Key::LitterExpiry).as_deref(), Some("72h"));
+        assert_eq!(profile.retries, Some(2));
+        assert!(profile.set(Key::Concurrency, Some("many")).is_err());
+        assert!(profile.set(Key::LitterExpiry, Some("2d")).is_err());
+        profile.set(Key::Retries, None).unwrap();
+        assert_eq!(profile.get(Key::Retries), None);
+
+        let args = parse_args(["catbox", "config", "set", "timeout", "30"]);
+        assert!(matches!(args.command, CatboxCommand::Config(_)));
+        assert!(profile.set(Key::UserHash, Some("hash")).is_err());
+        profile.user_hash = Some("1234567890".to_string());
+        profile.set(Key::UserHash, None).unwrap();
+        assert_eq!(profile.user_hash, None);
+        assert_eq!(config::masked("1234567890"), "******7890");
+        assert_eq!(config::masked("abc"), "abc");
+        assert!(try_parse_args(["catbox", "config", "get", "unknown"]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn config_permissions() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("catbox").join("config.toml");
+        let login_args = || match CatboxArgs::parse_from([
+            "catbox",
+            "login",
+            "--user",
+            "hash",
+            "--profile",
+            "work",
+            "--config",
+            path.to_str().unwrap(),
+        ])
+        .command
+        {
+            CatboxCommand::Login(login_args) => login_args,
+            _ => panic!("Invalid subcommand"),
+        };
+        login(login_args())?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        let config = config::Config::load(&path)?;
+        assert_eq!(
+            config.profile(Some("work"))?.user_hash.as_deref(),
+            Some("hash")
+        );
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        assert!(config::Config::load(&path).is_err());
+        login(login_args())?;
+        assert!(config::Config::load(&path).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_formats() {
+        let started = Instant::now();
+        let tasks = [30, 0, 10].map(|delay| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            Report::new("Uploading", format!("file\t{}", delay), started).size(Some(delay))
+        });
+        let bars = UploadBars::new(&[]);
+        let reports = run_reports(tasks, 3, OutputFormat::Json, &bars).await;
+        let inputs: Vec<_> = reports.iter().map(|report| report.input.as_str()).collect();
+        assert_eq!(inputs, ["file\t30", "file\t0", "file\t10"]);
+
+        let failed = Report::new("Uploading", "a.exe", started)
+            .failed(CatboxError::BlockedExtension("exe".to_string()));
+        assert_eq!(
+            failed.text(),
+            "Uploading a.exe failed: Files with the .exe extension are not allowed"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&OutputFormat::Jsonl.line(&failed).unwrap()).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["input"], "a.exe");
+        assert!(json["url"].is_null());
+
+        let tsv = OutputFormat::Tsv.line(&reports[0]).unwrap();
+        assert!(tsv.starts_with("file\\t30\tok\t\t\t\t30\t"));
+        assert_eq!(
+            OutputFormat::Tsv.header().unwrap().split('\t').count(),
+            tsv.split('\t').count()
+        );
+        assert!(OutputFormat::Json.line(&failed).is_none());
+
+        let mut profile = Profile::default();
+        profile.set(Key::Output, Some("JSONL")).unwrap();
+        assert_eq!(profile.output, Some(OutputFormat::Jsonl));
+        assert!(profile.set(Key::Output, Some("xml")).is_err());
+    }
+
+    #[tokio::test]
+    async fn exit_codes() {
+        let started = Instant::now();
+        let ok = Report::new("Uploading", "a.png", started);
+        let failed = Report::new("Uploading", "b.exe", started)
+            .failed(CatboxError::BlockedExtension("exe".to_string()));
+        let skipped = Report::skipped("c", "Not a file or valid URL");
+        assert_eq!(
+            exit::check_batch(&[ok.clone(), failed.clone()])
+                .unwrap_err()
+                .exit,
+            Exit::Partial
+        );
+        assert_eq!(
+            exit::check_batch(&[failed, skipped.clone()])
+                .unwrap_err()
+                .exit,
+            Exit::Failure
+        );
+        // Skipped inputs are neither failures nor successes
+        assert!(exit::check_batch(&[ok.clone(), skipped.clone()]).is_ok());
+        assert!(exit::check_batch(&[skipped]).is_ok());
+        assert!(exit::check_batch(&[ok]).is_ok());
+
+        // Catbox refusing the user hash is an authentication failure
+        let refused = || CatboxError::Api("No album found for user specified.".to_string());
+        let err: Box<dyn Error> = refused().into();
+        assert_eq!(exit::exit_code(err.as_ref()), Exit::Auth);
+        let refused = Report::new("Adding files to album", "xyz789", started).failed(refused());
+        assert_eq!(
+            exit::check_batch(&[refused.clone(), refused])
+                .unwrap_err()
+                .exit,
+            Exit::Auth
+        );
+
+        let err = require_user_hash(None, &Profile::default()).unwrap_err();
+        if env::var("CATBOX_USER_HASH").is_err() {
+            assert_eq!(err.exit, Exit::Auth);
+        }
+        let profile = Profile {
+            user_hash: Some("hash".to_string()),
+            ..Profile::default()
+        };
+        assert!(require_user_hash(None, &profile).is_ok());
+
+        let err: Box<dyn Error> = "Something else".into();
+        assert_eq!(exit::exit_code(err.as_ref()), Exit::Failure);
+        assert!(
+            try_parse_args(["catbox", "upload"])
+                .unwrap_err()
+                .use_stderr()
+        );
+    }
+
+    #[test]
+    fn stdin_names() {
+        let gzip = b"\x1f\x8b\x08\0\0\0\0\0";
+        assert_eq!(stdin_name(None, b"text"), "stdin");
+        assert_eq!(stdin_name(None, gzip), "stdin.gz");
+        assert_eq!(stdin_name(Some("dump".to_string()), gzip), "dump.gz");
+        assert_eq!(stdin_name(Some("db.sql.gz".to_string()), gzip), "db.sql.gz");
+        assert_eq!(stdin_name(Some("notes.txt".to_string()), gzip), "notes.txt");
+
+        let files = |files: &[&str]| {
+            files
+                .iter()
+                .map(|file| file.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(check_stdin(&files(&["-", "a.png"]), &Some("b".to_string())).is_ok());
+        assert!(check_stdin(&files(&["a.png"]), &None).is_ok());
+        assert_eq!(
+            check_stdin(&files(&["a.png"]), &Some("b".to_string()))
+                .unwrap_err()
+                .exit,
+            Exit::Usage
+        );
+        assert!(check_stdin(&files(&["-", "-"]), &None).is_err());
+    }
+
+    #[test]
+    fn globs() {
+        use walk::Glob;
+
+        assert!(Glob::new("*.png").matches("photos/cat.png"));
+        assert!(!Glob::new("*.png").matches("photos/cat.jpg"));
+        assert!(Glob::new("photos/*.jp[e]g").matches("photos/cat.jpeg"));
+        assert!(!Glob::new("photos/*.jpg").matches("photos/2023/cat.jpg"));
+        assert!(Glob::new("photos/**/*.jpg").matches("photos/2023/05/cat.jpg"));
+        assert!(Glob::new("photos/**/*.jpg").matches("photos/cat.jpg"));
+        assert!(Glob::new("/build").matches("build"));
+        assert!(Glob::new("file?.[!a-c]*").matches("file1.dat"));
+        assert!(!Glob::new("file?.[!a-c]*").matches("file1.bat"));
+        assert!(Glob::new("[abc").matches("[abc"));
+    }
+
+    #[test]
+    fn walk_directories() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        for file in [
+            "a.png",
+            "b.txt",
+            ".hidden.png",
+            "sub/c.png",
+            "sub/d.log",
+            "build/e.png",
+            "skip/f.png",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, "content")?;
+        }
+        std::fs::write(dir.path().join(".gitignore"), "build/\n*.log\n")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("a.png"), dir.path().join("link.png"))?;
+
+        let walk = |args: &[&str]| -> Vec<String> {
+            let args = parse_args(
+                ["catbox", "upload", "--recursive"]
+                    .iter()
+                    .chain(args)
+                    .chain([&dir.path().to_str().unwrap()]),
+            );
+            match args.command {
+                CatboxCommand::Upload(upload_args) => Walker::new(&upload_args.walk_args)
+                    .walk(dir.path())
+                    .into_iter()
+                    .map(|walked| {
+                        let path = walked.unwrap();
+                        let path = path.strip_prefix(dir.path()).unwrap();
+                        path.to_str().unwrap().replace('\\', "/")
+                    })
+                    .collect(),
+                _ => panic!("Invalid subcommand"),
+            }
+        };
+        assert_eq!(
+            walk(&[]),
+            [
+                "a.png",
+                "b.txt",
+                "build/e.png",
+                "skip/f.png",
+                "sub/c.png",
+                "sub/d.log"
+            ]
+        );
+        assert_eq!(
+            walk(&["--gitignore", "--include", "*.png", "--exclude", "skip"]),
+            ["a.png", "sub/c.png"]
+        );
+        assert_eq!(
+            walk(&["--hidden", "--include", "*.png", "--exclude", "*/*"]),
+            [".hidden.png", "a.png"]
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            walk(&[
+                "--symlinks",
+                "files",
+                "--include",
+                "*.png",
+                "--exclude",
+                "*/*"
+            ]),
+            ["a.png", "link.png"]
+        );
+
+        let inputs = expand_inputs(
+            vec![dir.path().to_str().unwrap().to_string()],
+            &WalkArgs {
+                recursive: false,
+                include: Vec::new(),
+                exclude: Vec::new(),
+                gitignore: false,
+                hidden: false,
+                symlinks: walk::Symlinks::Skip,
+            },
+        );
+        assert!(matches!(&inputs[..], [Input::Done(report)] if report.status == Status::Failed));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_album() -> Result<(), Box<dyn Error>> {
+        let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
+        write!(file, "content").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let args = parse_args([
+            "catbox", "upload", "--album", "Title", "--desc", "Text", path, "missing",
+        ]);
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(upload_args.album.as_deref(), Some("Title"));
+            let err = upload(upload_args).await.unwrap_err();
+            assert_eq!(exit::exit_code(err.as_ref()), Exit::Partial);
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let client = CatboxClient::builder().build();
+        let upload = |input: Input| {
+            let client = &client;
+            async move {
+                match input {
+                    Input::Uri(uri) => super::upload_url(uri, client).await,
+                    Input::Done(report) => report,
+                }
+            }
+        };
+        let inputs = || {
+            vec![
+                Input::Done(invalid_uri("missing".to_string())),
+                Input::Uri("https://example.com/cat.png".to_string()),
+            ]
+        };
+        let (reports, album) = super::upload_album(
+            "Title".to_string(),
+            String::new(),
+            inputs(),
+            upload,
+            2,
+            &client,
+        )
+        .await;
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].input, "missing");
+        assert_eq!(reports[1].id.as_deref(), Some("file.png"));
+        assert_eq!(album.status, Status::Ok);
+        assert_eq!(album.id.as_deref(), Some("123435"));
+
+        let (reports, album) = super::upload_album(
+            "Title".to_string(),
+            String::new(),
+            inputs().into_iter().take(1).collect(),
+            upload,
+            2,
+            &client,
+        )
+        .await;
+        assert_eq!(reports[0].status, Status::Failed);
+        assert_eq!(album.status, Status::Failed);
+        assert_eq!(album.error.as_deref(), Some("No files were uploaded"));
+
+        assert!(try_parse_args(["catbox", "upload", "--desc", "Text", path]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let dir_path = dir.path().to_str().unwrap();
+        let download_args = |conflict: &str| {
+            let args = parse_args([
+                "catbox",
+                "download",
+                "--dir",
+                dir_path,
+                "--on-conflict",
+                conflict,
+                "abc.png",
+                "https://catbox.moe/c/123asd",
+                "https://files.catbox.moe/abc.png",
+                "https://litter.catbox.moe/abc.png",
+            ]);
+            match args.command {
+                CatboxCommand::Download(download_args) => download_args,
+                _ => panic!("Invalid subcommand"),
+            }
+        };
+
+        // The same file twice is downloaded once, another file of the same name is
+        // skipped, which isn't a failure
+        download(download_args("skip")).await?;
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("abc.png"))?,
+            "https://files.catbox.moe/abc.png"
+        );
+        assert!(dir.path().join("file.png").exists());
+
+        download(download_args("rename")).await?;
+        assert!(dir.path().join("abc-1.png").exists());
+        assert!(dir.path().join("file-1.png").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("abc-2.png"))?,
+            "https://litter.catbox.moe/abc.png"
+        );
+
+        let mut targets = Targets::new(dir.path(), download::Conflict::Overwrite);
+        let file: FileUrl = "abc.png".parse()?;
+        assert_eq!(targets.path(&file), Ok(dir.path().join("abc.png")));
+        assert_eq!(targets.path(&file), Ok(dir.path().join("abc-3.png")));
+        assert!("..".parse::<FileUrl>().is_err());
+
+        let source = |input| Source::parse(input, "https://catbox.moe/c/", "https://example.com/");
+        assert_eq!(source("123asd")?, Source::Album("123asd".parse()?));
+        assert_eq!(
+            source("catbox.moe/c/123asd")?,
+            Source::Album("123asd".parse()?)
+        );
+        assert_eq!(
+            source("abc.png")?,
+            Source::File("https://example.com/abc.png".parse()?)
+        );
+        assert_eq!(
+            source("https://litter.catbox.moe/abc.png")?,
+            Source::File("https://litter.catbox.moe/abc.png".parse()?)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn album_sync() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("a.png"), "a")?;
+        std::fs::write(dir.path().join("b.txt"), "b")?;
+        std::fs::write(dir.path().join("sub").join("c.png"), "c")?;
+        std::fs::write(dir.path().join("sub").join("copy.png"), "c")?;
+        let state_path = dir.path().join(sync::STATE_FILE);
+        let sync_args = |flags: &[&str]| {
+            let args = parse_args(
+                [
+                    "catbox", "album", "sync", "--user", "123345", "--short", "123asd",
+                ]
+                .iter()
+                .chain(flags)
+                .chain([&dir.path().to_str().unwrap()]),
+            );
+            match args.command {
+                CatboxCommand::Album(album_args) => album_args,
+                _ => panic!("Invalid subcommand"),
+            }
+        };
+
+        parse_album(sync_args(&["--dry-run"])).await?;
+        assert!(!state_path.exists());
+
+        parse_album(sync_args(&[])).await?;
+        let state = State::load(&state_path)?;
+        assert_eq!(state.album.as_deref(), Some("123asd"));
+        let paths: Vec<_> = state
+            .files
+            .values()
+            .map(|file| file.path.as_str())
+            .collect();
+        assert_eq!(paths.len(), 3);
+        assert!(paths.contains(&"sub/c.png") || paths.contains(&"sub/copy.png"));
+
+        // Uploaded files are only added to the album again, the state file isn't synced
+        let album = CatboxClient::builder()
+            .build()
+            .get_album("123asd".parse()?)
+            .await?;
+        let (local, _) = sync::local_files(
+            dir.path(),
+            &Walker::new(&WalkArgs {
+                recursive: true,
+                include: Vec::new(),
+                exclude: Vec::new(),
+                gitignore: false,
+                hidden: true,
+                symlinks: Symlinks::Skip,
+            }),
+            &state_path,
+        )
+        .await;
+        assert_eq!(local.len(), 4);
+        let plan = Plan::new(&local, &state, &album, true);
+        assert!(plan.upload.is_empty());
+        assert_eq!(plan.add.len(), 1);
+        assert_eq!(plan.add[0].1.as_str(), "file.txt");
+
+        // Files gone locally are removed, and deleted if asked to
+        std::fs::remove_file(dir.path().join("a.png"))?;
+        let plan = Plan::new(&local[1..], &state, &album, true);
+        assert_eq!(plan.remove.len(), 1);
+        assert!(
+            Plan::new(&local[1..], &state, &album, false)
+                .remove
+                .is_empty()
+        );
+        parse_album(sync_args(&["--delete-missing"])).await?;
+        assert_eq!(State::load(&state_path)?.files.len(), 2);
+
+        let args = parse_args([
+            "catbox",
+            "album",
+            "sync",
+            "--user",
+            "123345",
+            "--short",
+            "other",
+            dir.path().to_str().unwrap(),
+        ]);
+        let CatboxCommand::Album(album_args) = args.command else {
+            panic!("Invalid subcommand");
+        };
+        let err = parse_album(album_args).await.unwrap_err();
+        assert!(err.to_string().contains("belongs to the album 123asd"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_litter_time() {
+        assert!(try_parse_args(["catbox", "litter", "--time", "2d", "file"]).is_err());
+    }
+
     #[tokio::test]
     #[should_panic]
     async fn invalid_command() {
@@ -0,0 +1,305 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+    str::FromStr,
+    time::Instant,
+};
+
+use catbox::{Album, AlbumUrl, CatboxError, FileUrl};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::exit::{self, Exit};
+
+static TSV_HEADER: &[&str] = &[
+    "input",
+    "status",
+    "url",
+    "id",
+    "error",
+    "size",
+    "elapsed_ms",
+];
+
+/// How the results of a command are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A line of text per result, as it's done
+    #[default]
+    Text,
+    /// A JSON array of all results in the order of the inputs, when all are done
+    Json,
+    /// A JSON object per line, as each result is done
+    Jsonl,
+    /// Tab-separated values with a header, a line per result as it's done
+    Tsv,
+}
+
+/// Contents of an album as printed in JSON
+#[derive(Debug, Serialize)]
+struct AlbumListing<'a> {
+    url: &'a str,
+    id: &'a str,
+    title: &'a str,
+    description: &'a str,
+    files: Vec<&'a str>,
+}
+
+/// What happened to an input of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+/// Result of a command for one of its inputs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Path, URL, ID or title given to the command
+    pub input: String,
+    pub status: Status,
+    /// Link to the uploaded file or album
+    pub url: Option<String>,
+    /// ID of the uploaded file or album
+    pub id: Option<String>,
+    pub error: Option<String>,
+    /// Size of the uploaded file in bytes
+    pub size: Option<u64>,
+    /// Milliseconds taken until the result was known
+    pub elapsed_ms: u64,
+    /// Response of the server if it isn't a link, e.g. after deleting files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Where a downloaded file was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// What was done to the input, used for describing failures
+    #[serde(skip)]
+    action: &'static str,
+    /// Exit code for the failure, if it failed
+    #[serde(skip)]
+    exit: Exit,
+}
+
+impl Report {
+    /// Start a report of doing `action` to `input`, e.g. "Uploading" a path
+    pub fn new(action: &'static str, input: impl Into<String>, started: Instant) -> Self {
+        Self {
+            input: input.into(),
+            status: Status::Ok,
+            url: None,
+            id: None,
+            error: None,
+            size: None,
+            elapsed_ms: started.elapsed().as_millis().try_into().unwrap_or(u64::MAX),
+            message: None,
+            path: None,
+            action,
+            exit: Exit::Failure,
+        }
+    }
+
+    /// Report of an input that wasn't used
+    pub fn skipped(input: impl Into<String>, reason: impl Display) -> Self {
+        let mut report = Self::new("Ignoring", input, Instant::now());
+        report.status = Status::Skipped;
+        report.error = Some(reason.to_string());
+        report
+    }
+
+    pub fn file(mut self, url: &FileUrl) -> Self {
+        self.url = Some(url.to_string());
+        self.id = Some(url.id().to_string());
+        self
+    }
+
+    pub fn album(mut self, url: &AlbumUrl) -> Self {
+        self.url = Some(url.to_string());
+        self.id = Some(url.short().to_string());
+        self
+    }
+
+    pub fn message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    pub fn path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_string_lossy().to_string());
+        self
+    }
+
+    pub fn size(mut self, size: Option<u64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn failed(self, error: CatboxError) -> Self {
+        let exit = exit::error_exit(&error);
+        Self {
+            exit,
+            ..self.failed_because(error)
+        }
+    }
+
+    /// Failure that isn't caused by a request
+    pub fn failed_because(mut self, reason: impl Display) -> Self {
+        self.status = Status::Failed;
+        self.error = Some(reason.to_string());
+        self
+    }
+
+    /// Exit code for the failure, if it failed
+    pub fn exit(&self) -> Exit {
+        self.exit
+    }
+
+    /// Line printed in the text format
+    pub fn text(&self) -> String {
+        let error = self.error.as_deref().unwrap_or_default();
+        match self.status {
+            Status::Ok => self
+                .path
+                .clone()
+                .or_else(|| self.url.clone())
+                .or_else(|| self.message.clone())
+                .unwrap_or_default(),
+            Status::Failed => format!("{} {} failed: {}", self.action, self.input, error),
+            Status::Skipped => format!("{} {}: {}", self.action, self.input, error),
+        }
+    }
+
+    fn tsv(&self) -> String {
+        [
+            self.input.clone(),
+            self.status.to_string(),
+            self.url.clone().unwrap_or_default(),
+            self.id.clone().unwrap_or_default(),
+            self.error.clone().unwrap_or_default(),
+            self.size.map(|size| size.to_string()).unwrap_or_default(),
+            self.elapsed_ms.to_string(),
+        ]
+        .map(|field| tsv_field(&field))
+        .join("\t")
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        })
+    }
+}
+
+impl OutputFormat {
+    /// Printed before any results
+    pub fn header(&self) -> Option<String> {
+        match self {
+            OutputFormat::Tsv => Some(TSV_HEADER.join("\t")),
+            _ => None,
+        }
+    }
+
+    /// Printed as soon as `report` is done
+    pub fn line(&self, report: &Report) -> Option<String> {
+        match self {
+            OutputFormat::Text => Some(report.text()),
+            OutputFormat::Jsonl => serde_json::to_string(report).ok(),
+            OutputFormat::Tsv => Some(report.tsv()),
+            OutputFormat::Json => None,
+        }
+    }
+
+    /// Printed after all results, given in the order of the inputs
+    pub fn footer(&self, reports: &[Report]) -> Option<String> {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(reports).ok(),
+            _ => None,
+        }
+    }
+
+    /// The title, description and files of an album
+    ///
+    /// Text has the title and description followed by a blank line and the file URLs,
+    /// TSV a line per file
+    pub fn album(&self, album: &Album) -> String {
+        let listing = AlbumListing {
+            url: album.url.as_str(),
+            id: album.url.short().as_str(),
+            title: &album.title,
+            description: &album.description,
+            files: album.files.iter().map(FileUrl::as_str).collect(),
+        };
+        match self {
+            OutputFormat::Text => [album.title.as_str(), album.description.as_str()]
+                .into_iter()
+                .filter(|line| !line.is_empty())
+                .chain([""])
+                .chain(listing.files)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            OutputFormat::Json => serde_json::to_string_pretty(&listing).unwrap_or_default(),
+            OutputFormat::Jsonl => serde_json::to_string(&listing).unwrap_or_default(),
+            OutputFormat::Tsv => ["url\tid".to_string()]
+                .into_iter()
+                .chain(album.files.iter().map(|file| {
+                    format!(
+                        "{}\t{}",
+                        tsv_field(file.as_str()),
+                        tsv_field(file.id().as_str())
+                    )
+                }))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Print the results of a command that didn't print them as they were done
+    pub fn print(&self, reports: &[Report]) {
+        let lines = self
+            .header()
+            .into_iter()
+            .chain(reports.iter().filter_map(|report| self.line(report)))
+            .chain(self.footer(reports));
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.pad(value.get_name()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true).map_err(|_| {
+            format!(
+                "{} is not a valid format (Options: text, json, jsonl, tsv)",
+                s
+            )
+        })
+    }
+}
+
+/// Escape the characters that would break a TSV line
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
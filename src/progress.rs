@@ -0,0 +1,155 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use catbox::Progress;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+static TEMPLATE: &str = "{msg:20!} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta}";
+
+/// Progress bars for a batch of file uploads: one per file and,
+/// for more than one file, one for the whole batch.
+///
+/// Bars are only drawn when stdout is a terminal. Otherwise they are hidden
+/// and only the result lines are printed.
+pub struct UploadBars {
+    multi: MultiProgress,
+    total: Option<ProgressBar>,
+}
+
+/// Progress bar of a single upload, removed when dropped
+pub struct FileBar {
+    bar: ProgressBar,
+    total: Option<ProgressBar>,
+}
+
+impl UploadBars {
+    pub fn new(files: &[String]) -> Self {
+        let target = match io::stdout().is_terminal() {
+            true => ProgressDrawTarget::stdout(),
+            false => ProgressDrawTarget::hidden(),
+        };
+        let multi = MultiProgress::with_draw_target(target);
+        let total = (files.len() > 1).then(|| {
+            let size = files
+                .iter()
+                .filter_map(|file| fs::metadata(file).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            let bar = multi.add(ProgressBar::new(size).with_style(style()));
+            bar.set_message("Total");
+            bar
+        });
+        Self { multi, total }
+    }
+
+    /// Add a bar for uploading `file`
+    pub fn file(&self, file: &str) -> FileBar {
+        let bar = ProgressBar::new(0).with_style(style());
+        let bar = match &self.total {
+            Some(total) => self.multi.insert_before(total, bar),
+            None => self.multi.add(bar),
+        };
+        bar.set_message(
+            Path::new(file)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+        FileBar {
+            bar,
+            total: self.total.clone(),
+        }
+    }
+
+    /// Print a line above the bars
+    pub fn println(&self, line: &str) {
+        self.multi.suspend(|| println!("{}", line));
+    }
+
+    /// Print a line to stderr above the bars, keeping stdout for the results
+    pub fn eprintln(&self, line: &str) {
+        self.multi.suspend(|| eprintln!("{}", line));
+    }
+
+    pub fn finish(&self) {
+        if let Some(total) = &self.total {
+            total.finish_and_clear();
+        }
+        let _ = self.multi.clear();
+    }
+}
+
+impl FileBar {
+    /// Callback updating this bar and the total from an upload's progress.
+    /// When a retry starts the upload over, its bytes are taken off the total.
+    pub fn callback(&self) -> impl Fn(Progress) + Send + Sync + 'static {
+        let bar = self.bar.clone();
+        let total = self.total.clone();
+        let previous = Arc::new(AtomicU64::new(0));
+        move |progress| {
+            bar.set_length(progress.total);
+            let previous = previous.swap(progress.sent, Ordering::Relaxed);
+            if let Some(total) = &total {
+                match progress.sent >= previous {
+                    true => total.inc(progress.sent - previous),
+                    false => total
+                        .set_position(total.position().saturating_sub(previous - progress.sent)),
+                }
+            }
+            bar.set_position(progress.sent);
+        }
+    }
+}
+
+impl Drop for FileBar {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn style() -> ProgressStyle {
+    ProgressStyle::with_template(TEMPLATE)
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_take_bytes_off_the_total() {
+        let bars = UploadBars::new(&["a".to_string(), "b".to_string()]);
+        let first = bars.file("a");
+        let second = bars.file("b");
+        let (first, second) = (first.callback(), second.callback());
+        let total = bars.total.clone().unwrap();
+
+        first(Progress {
+            sent: 60,
+            total: 100,
+        });
+        second(Progress {
+            sent: 30,
+            total: 50,
+        });
+        assert_eq!(total.position(), 90);
+        first(Progress {
+            sent: 0,
+            total: 100,
+        });
+        assert_eq!(total.position(), 30);
+        first(Progress {
+            sent: 100,
+            total: 100,
+        });
+        assert_eq!(total.position(), 130);
+    }
+}
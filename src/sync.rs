@@ -0,0 +1,175 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use catbox::{Album, AlbumShort, FileId, history::hash_file};
+use serde::{Deserialize, Serialize};
+
+use crate::walk::Walker;
+
+/// Name of the state file in a synced directory, unless another path is given
+pub static STATE_FILE: &str = ".catbox-sync.json";
+
+/// Files of a directory uploaded by `catbox album sync`, saved between runs
+///
+/// Files are keyed by the SHA-256 of their contents, so a file that is moved or
+/// renamed isn't uploaded again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    /// Album the directory is synced with
+    pub album: Option<String>,
+    pub files: BTreeMap<String, Synced>,
+}
+
+/// An uploaded file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Synced {
+    /// Catbox ID of the file
+    pub id: String,
+    /// Path of the file relative to the directory when it was last seen
+    pub path: String,
+}
+
+/// A file found in the synced directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub path: PathBuf,
+    /// Path relative to the synced directory, separated with `/`
+    pub relative: String,
+    /// Hex-encoded SHA-256 of the contents
+    pub hash: String,
+}
+
+/// What `catbox album sync` does to bring the album up to date
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    /// Files that were never uploaded
+    pub upload: Vec<Local>,
+    /// Uploaded files that are missing from the album
+    pub add: Vec<(Local, FileId)>,
+    /// Uploaded files in the album whose local files are gone, by their hashes
+    pub remove: Vec<(String, Synced)>,
+}
+
+impl State {
+    /// Read the state file. A missing file is an empty state.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|err| format!("Invalid state file {}: {}", path.display(), err).into()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err).into()),
+        }
+    }
+
+    /// Write the state file, replacing the old one only once the new one is complete
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|err| format!("Couldn't write {}: {}", path.display(), err).into())
+    }
+
+    /// Check that the state isn't of another album, and claim it for `short` if it's new
+    pub fn check_album(&mut self, short: &AlbumShort, path: &Path) -> Result<(), String> {
+        match &self.album {
+            Some(album) if album != short.as_str() => Err(format!(
+                "{} belongs to the album {}. Give another file with --state to sync {}.",
+                path.display(),
+                album,
+                short
+            )),
+            _ => {
+                self.album = Some(short.to_string());
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Hash the files found by `walker` in `dir`, leaving out the state file
+///
+/// Returns the files, and the paths that couldn't be read with the errors
+pub async fn local_files(
+    dir: &Path,
+    walker: &Walker,
+    state_path: &Path,
+) -> (Vec<Local>, Vec<(PathBuf, io::Error)>) {
+    let state_path = state_path.canonicalize().ok();
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+    for walked in walker.walk(dir) {
+        let path = match walked {
+            Ok(path) if path.canonicalize().ok() == state_path && state_path.is_some() => continue,
+            Ok(path) => path,
+            Err(err) => {
+                failed.push(err);
+                continue;
+            },
+        };
+        match hash_file(&path.to_string_lossy()).await {
+            Ok((_, hash)) => files.push(Local {
+                relative: relative_path(dir, &path),
+                path,
+                hash,
+            }),
+            Err(err) => failed.push((path, err)),
+        }
+    }
+    (files, failed)
+}
+
+fn relative_path(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Plan {
+    /// Compare the local files with what was uploaded before and what the album has
+    ///
+    /// Files in the album that weren't uploaded by syncing are left alone. Files
+    /// whose local files are gone are only removed if `remove_missing` is set.
+    pub fn new(local: &[Local], state: &State, album: &Album, remove_missing: bool) -> Self {
+        let in_album: HashSet<_> = album.files.iter().map(|file| file.id().as_str()).collect();
+        let mut plan = Plan::default();
+        let mut seen = HashSet::new();
+        for file in local {
+            // Copies of a file are uploaded once
+            if !seen.insert(file.hash.as_str()) {
+                continue;
+            }
+            match state.files.get(&file.hash) {
+                None => plan.upload.push(file.clone()),
+                Some(synced) if !in_album.contains(synced.id.as_str()) => {
+                    if let Ok(id) = synced.id.parse() {
+                        plan.add.push((file.clone(), id));
+                    }
+                },
+                Some(_) => {},
+            }
+        }
+        if remove_missing {
+            plan.remove = state
+                .files
+                .iter()
+                .filter(|(hash, synced)| {
+                    !seen.contains(hash.as_str()) && in_album.contains(synced.id.as_str())
+                })
+                .map(|(hash, synced)| (hash.clone(), synced.clone()))
+                .collect();
+        }
+        plan
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upload.is_empty() && self.add.is_empty() && self.remove.is_empty()
+    }
+}
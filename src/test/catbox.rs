@@ -1,122 +1,388 @@
-pub mod album {
-    use std::error::Error;
+use std::{error::Error, fs::File, future::Future, io, path::Path, time::Duration};
 
-    pub async fn create<S: Into<String>>(
-        title: S,
-        desc: S,
-        user_hash: Option<S>,
-        files: Vec<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = (title, desc, user_hash, files);
-        Ok("https://catbox.moe/c/123435".to_string())
+use bytes::Bytes;
+use catbox::{
+    Album, AlbumChanges, AlbumShort, AlbumUpload, AlbumUrl, CatboxError, ContentSniffing,
+    ContentType, FileId, FileUrl, History, LitterExpiry, Progress, RetryPolicy, UploadLimits,
+};
+use futures::{TryStream, TryStreamExt};
+use url::Url;
+
+#[derive(Debug, Clone, Default)]
+pub struct CatboxClient {
+    user_hash: Option<String>,
+    files_url: Option<String>,
+    album_url: Option<String>,
+    upload_limits: UploadLimits,
+    content_sniffing: ContentSniffing,
+}
+
+#[derive(Debug, Default)]
+pub struct CatboxClientBuilder {
+    user_hash: Option<String>,
+    files_url: Option<String>,
+    album_url: Option<String>,
+    upload_limits: UploadLimits,
+    content_sniffing: ContentSniffing,
+}
+
+impl CatboxClient {
+    pub fn builder() -> CatboxClientBuilder {
+        CatboxClientBuilder::default()
     }
 
-    pub async fn delete<S: Into<String>>(short: S, user_hash: S) -> Result<String, Box<dyn Error>> {
-        let _ = (short, user_hash);
-        Ok(String::new())
+    pub fn content_sniffing(&self) -> ContentSniffing {
+        self.content_sniffing
     }
 
-    pub async fn edit<S: Into<String>>(
-        short: S,
-        title: S,
-        desc: S,
-        user_hash: S,
-        files: Vec<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = (title, desc, user_hash, files);
-        let short = short.into();
-        Ok(match short.len() > 0 {
-            true => format!("https://catbox.moe/c/{}", short),
-            false => "No album found for user specified.".to_string(),
-        })
+    pub fn files_url(&self) -> &str {
+        self.files_url
+            .as_deref()
+            .unwrap_or("https://files.catbox.moe/")
     }
 
-    pub async fn add_files<S: Into<String>>(
-        short: S,
-        user_hash: S,
-        files: Vec<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = (user_hash, files);
-        let short = short.into();
-        Ok(match short.len() > 0 {
-            true => format!("https://catbox.moe/c/{}", short),
-            false => "No album found for user specified.".to_string(),
-        })
+    pub fn album_url(&self) -> &str {
+        self.album_url.as_deref().unwrap_or("https://catbox.moe/c/")
     }
 
-    pub async fn remove_files<S: Into<String>>(
-        short: S,
-        user_hash: S,
-        files: Vec<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = (user_hash, files);
-        let short = short.into();
-        Ok(match short.len() > 0 {
-            true => format!("https://catbox.moe/c/{}", short),
-            false => "No album found for user specified.".to_string(),
+    async fn upload_name(&self, file_path: &str) -> Result<String, CatboxError> {
+        let content_type = match self.content_sniffing {
+            ContentSniffing::FixExtension => ContentType::detect_file(file_path).await?,
+            _ => None,
+        };
+        Ok(match content_type {
+            Some(content_type) => content_type.fix_name(file_path),
+            None => file_path.to_string(),
         })
     }
-}
-
-pub mod file {
-    use std::{error::Error, fs::File};
 
-    use url::Url;
+    pub async fn upload_file<S: Into<String>>(&self, file_path: S) -> Result<FileUrl, CatboxError> {
+        let file_path = file_path.into();
+        let size = File::open(&file_path)?.metadata()?.len();
+        let name = self.upload_name(&file_path).await?;
+        self.upload_limits.check_catbox(&name, Some(size))?;
+        Ok(format!(
+            "https://catbox.moe/file.{}",
+            name.split('.').next_back().unwrap()
+        )
+        .parse()
+        .unwrap())
+    }
 
-    pub async fn from_file<S: Into<String>>(
+    pub async fn upload_file_with_progress<S, F>(
+        &self,
         file_path: S,
-        user_hash: Option<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = user_hash;
+        progress: F,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        S: Into<String>,
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
         let file_path = file_path.into();
-        File::open(&file_path)?;
+        let total = File::open(&file_path)?.metadata()?.len();
+        progress(Progress { sent: total, total });
+        self.upload_file(file_path).await
+    }
+
+    pub async fn upload_stream<T, S>(&self, stream: T, file_name: S) -> Result<FileUrl, CatboxError>
+    where
+        T: TryStream + Send + Sync + 'static,
+        T::Error: Into<Box<dyn Error + Send + Sync>>,
+        Bytes: From<T::Ok>,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        let size = stream_size(stream).await?;
+        self.upload_limits.check_catbox(&file_name, Some(size))?;
         Ok(format!(
             "https://catbox.moe/file.{}",
-            file_path.split(".").last().unwrap()
-        ))
+            file_name.split('.').next_back().unwrap()
+        )
+        .parse()
+        .unwrap())
     }
 
-    pub async fn from_url<S: Into<String>>(
-        url: S,
-        user_hash: Option<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = user_hash;
+    pub async fn upload_url<S: Into<String>>(&self, url: S) -> Result<FileUrl, CatboxError> {
         let url = url.into();
-        Url::parse(&url)?;
+        Url::parse(&url).map_err(|err| CatboxError::Api(err.to_string()))?;
         Ok(format!(
             "https://catbox.moe/file.{}",
-            url.split(".").last().unwrap()
-        ))
-    }
-
-    pub async fn delete<S: Into<String>>(
-        user_hash: S,
-        files: Vec<S>,
-    ) -> Result<String, Box<dyn Error>> {
-        let _ = user_hash;
-        let valid = files
-            .into_iter()
-            .map(|file| file.into())
-            .all(|file| file.len() > 0);
-        Ok(match valid {
-            true => "Files succesfully deleted.".to_string(),
-            false => "File doesn't exist?".to_string(),
-        })
+            url.split('.').next_back().unwrap()
+        )
+        .parse()
+        .unwrap())
     }
-}
 
-pub mod litter {
-    use std::{error::Error, fs::File};
+    pub async fn delete_files(&self, files: Vec<FileId>) -> Result<String, CatboxError> {
+        let valid = !files.is_empty();
+        match valid {
+            true => Ok("Files successfully deleted.".to_string()),
+            false => Err(CatboxError::Api("File doesn't exist?".to_string())),
+        }
+    }
 
-    pub async fn upload<S: Into<String>>(file_path: S, time: u8) -> Result<String, Box<dyn Error>> {
+    pub async fn upload_litter<S: Into<String>>(
+        &self,
+        file_path: S,
+        _time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError> {
         let file_path = file_path.into();
-        if ![1, 12, 24, 72].contains(&time) {
-            return Ok("Invalid time".to_string());
-        }
-        File::open(&file_path)?;
+        let size = File::open(&file_path)?.metadata()?.len();
+        self.upload_limits.check_litter(&file_path, Some(size))?;
+        Ok(format!(
+            "https://catbox.moe/file.{}",
+            file_path.split('.').next_back().unwrap()
+        )
+        .parse()
+        .unwrap())
+    }
+
+    pub async fn upload_litter_stream<T, S>(
+        &self,
+        stream: T,
+        file_name: S,
+        _time: LitterExpiry,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        T: TryStream + Send + Sync + 'static,
+        T::Error: Into<Box<dyn Error + Send + Sync>>,
+        Bytes: From<T::Ok>,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        let size = stream_size(stream).await?;
+        self.upload_limits.check_litter(&file_name, Some(size))?;
         Ok(format!(
             "https://catbox.moe/file.{}",
-            file_path.split(".").last().unwrap()
-        ))
+            file_name.split('.').next_back().unwrap()
+        )
+        .parse()
+        .unwrap())
+    }
+
+    pub async fn upload_litter_with_progress<S, F>(
+        &self,
+        file_path: S,
+        time: LitterExpiry,
+        progress: F,
+    ) -> Result<FileUrl, CatboxError>
+    where
+        S: Into<String>,
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        let file_path = file_path.into();
+        let total = File::open(&file_path)?.metadata()?.len();
+        progress(Progress { sent: total, total });
+        self.upload_litter(file_path, time).await
+    }
+
+    pub async fn get_album(&self, short: AlbumShort) -> Result<Album, CatboxError> {
+        Ok(Album {
+            url: AlbumUrl::new("https://catbox.moe/c/", short),
+            title: "My album".to_string(),
+            description: "A description".to_string(),
+            files: vec!["https://files.catbox.moe/file.png".parse().unwrap()],
+            unrecognized: Vec::new(),
+        })
+    }
+
+    pub async fn download_to_file_with_progress<F>(
+        &self,
+        url: &FileUrl,
+        path: &Path,
+        progress: F,
+    ) -> Result<u64, CatboxError>
+    where
+        F: Fn(Progress),
+    {
+        let contents = url.as_str().as_bytes();
+        std::fs::write(path, contents)?;
+        let total = contents.len() as u64;
+        progress(Progress { sent: total, total });
+        Ok(total)
+    }
+
+    pub async fn create_album<S: Into<String>>(
+        &self,
+        title: S,
+        desc: S,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let _ = (title, desc, files);
+        Ok("https://catbox.moe/c/123435".parse().unwrap())
+    }
+
+    pub async fn create_album_from_uploads<T, S, F, Fut>(
+        &self,
+        title: S,
+        desc: S,
+        inputs: Vec<T>,
+        _concurrency: usize,
+        upload: F,
+    ) -> Result<AlbumUpload<T>, CatboxError>
+    where
+        T: Clone,
+        S: Into<String>,
+        F: Fn(T) -> Fut,
+        Fut: Future<Output = Result<FileUrl, CatboxError>>,
+    {
+        let mut files = Vec::new();
+        let mut failed = Vec::new();
+        for input in inputs {
+            match upload(input.clone()).await {
+                Ok(url) => files.push(url),
+                Err(err) => failed.push((input, err)),
+            }
+        }
+        if files.is_empty() && !failed.is_empty() {
+            return Err(failed.swap_remove(0).1);
+        }
+        let ids = files.iter().map(|url| url.id().clone()).collect();
+        let url = self.create_album(title, desc, ids).await?;
+        Ok(AlbumUpload { url, files, failed })
+    }
+
+    pub async fn delete_album(&self, short: AlbumShort) -> Result<String, CatboxError> {
+        let _ = short;
+        Ok(String::new())
+    }
+
+    pub async fn edit_album<S: Into<String>>(
+        &self,
+        short: AlbumShort,
+        title: S,
+        desc: S,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let _ = (title, desc, files);
+        self.album_response(short)
+    }
+
+    pub async fn update_album(
+        &self,
+        short: AlbumShort,
+        changes: AlbumChanges,
+    ) -> Result<Album, CatboxError> {
+        let url = self.album_response(short.clone())?;
+        let mut album = self.get_album(short).await?;
+        for id in changes.add {
+            let file = FileUrl::new("https://files.catbox.moe/", id);
+            if !album.files.contains(&file) {
+                album.files.push(file);
+            }
+        }
+        album
+            .files
+            .retain(|file| !changes.remove.contains(file.id()));
+        Ok(Album {
+            url,
+            title: changes.title.unwrap_or(album.title),
+            description: changes.description.unwrap_or(album.description),
+            ..album
+        })
+    }
+
+    pub async fn add_to_album(
+        &self,
+        short: AlbumShort,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let _ = files;
+        self.album_response(short)
+    }
+
+    pub async fn remove_from_album(
+        &self,
+        short: AlbumShort,
+        files: Vec<FileId>,
+    ) -> Result<AlbumUrl, CatboxError> {
+        let _ = files;
+        self.album_response(short)
+    }
+
+    fn album_response(&self, short: AlbumShort) -> Result<AlbumUrl, CatboxError> {
+        match self.user_hash.is_some() {
+            true => Ok(AlbumUrl::new("https://catbox.moe/c/", short)),
+            false => Err(CatboxError::Api(
+                "No album found for user specified.".to_string(),
+            )),
+        }
+    }
+}
+
+impl CatboxClientBuilder {
+    pub fn user_hash<S: Into<String>>(mut self, user_hash: S) -> Self {
+        self.user_hash = Some(user_hash.into());
+        self
     }
+
+    pub fn catbox_api_url<S: Into<String>>(self, url: S) -> Self {
+        let _ = url.into();
+        self
+    }
+
+    pub fn litter_api_url<S: Into<String>>(self, url: S) -> Self {
+        let _ = url.into();
+        self
+    }
+
+    pub fn files_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.files_url = Some(url.into());
+        self
+    }
+
+    pub fn album_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.album_url = Some(url.into());
+        self
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        let _ = timeout;
+        self
+    }
+
+    pub fn retry_policy(self, policy: RetryPolicy) -> Self {
+        let _ = policy;
+        self
+    }
+
+    pub fn upload_limits(mut self, limits: UploadLimits) -> Self {
+        self.upload_limits = limits;
+        self
+    }
+
+    pub fn content_sniffing(mut self, sniffing: ContentSniffing) -> Self {
+        self.content_sniffing = sniffing;
+        self
+    }
+
+    pub fn history(self, history: History) -> Self {
+        let _ = history;
+        self
+    }
+
+    pub fn build(self) -> CatboxClient {
+        CatboxClient {
+            user_hash: self.user_hash,
+            files_url: self.files_url,
+            album_url: self.album_url,
+            upload_limits: self.upload_limits,
+            content_sniffing: self.content_sniffing,
+        }
+    }
+}
+
+/// Read all of `stream`, returning its size
+async fn stream_size<T>(stream: T) -> Result<u64, CatboxError>
+where
+    T: TryStream,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+    Bytes: From<T::Ok>,
+{
+    stream
+        .map_err(|err| CatboxError::Io(io::Error::other(err)))
+        .try_fold(0, |size, chunk| async move {
+            Ok(size + Bytes::from(chunk).len() as u64)
+        })
+        .await
 }
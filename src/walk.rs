@@ -0,0 +1,290 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::args::WalkArgs;
+
+/// What to do with symbolic links found in walked directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Symlinks {
+    /// Leave them out
+    #[default]
+    Skip,
+    /// Upload the files they point to, but don't enter linked directories
+    Files,
+    /// Upload linked files and walk linked directories
+    Follow,
+}
+
+/// Shell-style pattern for paths relative to a walked directory
+///
+/// `*` matches anything but `/`, `**` also matches across directories, `?` matches
+/// a single character and `[a-z]` or `[!a-z]` a set of characters. A pattern without
+/// a `/` is matched against the file name, others against the whole relative path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: Vec<char>,
+    anchored: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+        Self {
+            anchored: pattern.contains('/'),
+            pattern: pattern.trim_start_matches('/').chars().collect(),
+        }
+    }
+
+    /// Whether `path`, relative to the walked directory and separated with `/`, matches
+    pub fn matches(&self, path: &str) -> bool {
+        let path = match self.anchored {
+            true => path,
+            false => path.rsplit('/').next().unwrap_or(path),
+        };
+        match_chars(&self.pattern, &path.chars().collect::<Vec<_>>())
+    }
+}
+
+fn match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` can also match no directories at all
+            rest.strip_prefix(&['/'])
+                .is_some_and(|after| match_chars(after, text))
+                || (0..=text.len()).any(|i| match_chars(rest, &text[i..]))
+        },
+        ['*', rest @ ..] => {
+            let end = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=end).any(|i| match_chars(rest, &text[i..]))
+        },
+        ['?', rest @ ..] => match text {
+            [c, text @ ..] if *c != '/' => match_chars(rest, text),
+            _ => false,
+        },
+        ['[', class @ ..] => match (text, match_class(class, text.first().copied())) {
+            ([_, text @ ..], Some((true, length))) => match_chars(&class[length..], text),
+            (_, Some(_)) => false,
+            // An unclosed `[` is an ordinary character
+            _ => text.first() == Some(&'[') && match_chars(class, &text[1..]),
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            text.first() == Some(c) && match_chars(rest, &text[1..])
+        },
+    }
+}
+
+/// Match the character class after a `[` against `c`
+///
+/// Returns whether it matched and the length of the class including the `]`,
+/// or `None` if the class isn't closed
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let (negated, start) = match class.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut i = start;
+    while i < class.len() {
+        match &class[i..] {
+            [']', ..] if i > start => {
+                let matched = c.is_some_and(|c| c != '/' && matched != negated);
+                return Some((matched, i + 1));
+            },
+            [from, '-', to, ..] if *to != ']' => {
+                matched |= c.is_some_and(|c| (*from..=*to).contains(&c));
+                i += 3;
+            },
+            [other, ..] => {
+                matched |= c == Some(*other);
+                i += 1;
+            },
+            [] => break,
+        }
+    }
+    None
+}
+
+/// A line of a `.gitignore` file
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    glob: Glob,
+    /// Directory of the `.gitignore` relative to the walked one, ending with `/` unless empty
+    base: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str, base: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        Some(Self {
+            glob: Glob::new(line),
+            base: base.to_string(),
+            negated,
+            dir_only,
+        })
+    }
+
+    /// Whether the rule applies to `path`, relative to the walked directory
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only)
+            && path
+                .strip_prefix(&self.base)
+                .is_some_and(|path| self.glob.matches(path))
+    }
+}
+
+/// Finds the files to upload in directories
+#[derive(Debug, Clone)]
+pub struct Walker {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    gitignore: bool,
+    hidden: bool,
+    symlinks: Symlinks,
+}
+
+/// A file found by walking a directory, or a path that couldn't be read
+pub type Walked = Result<PathBuf, (PathBuf, io::Error)>;
+
+impl Walker {
+    pub fn new(walk_args: &WalkArgs) -> Self {
+        Self {
+            include: walk_args
+                .include
+                .iter()
+                .map(|glob| Glob::new(glob))
+                .collect(),
+            exclude: walk_args
+                .exclude
+                .iter()
+                .map(|glob| Glob::new(glob))
+                .collect(),
+            gitignore: walk_args.gitignore,
+            hidden: walk_args.hidden,
+            symlinks: walk_args.symlinks,
+        }
+    }
+
+    /// Files in `dir` and its subdirectories, sorted by name within each directory
+    pub fn walk(&self, dir: &Path) -> Vec<Walked> {
+        let mut found = Vec::new();
+        let mut visited = HashSet::new();
+        if let Ok(dir) = dir.canonicalize() {
+            visited.insert(dir);
+        }
+        self.walk_dir(dir, "", &[], &mut visited, &mut found);
+        found
+    }
+
+    fn walk_dir(
+        &self,
+        dir: &Path,
+        relative: &str,
+        rules: &[IgnoreRule],
+        visited: &mut HashSet<PathBuf>,
+        found: &mut Vec<Walked>,
+    ) {
+        let mut rules = rules.to_vec();
+        if self.gitignore {
+            if let Ok(contents) = fs::read_to_string(dir.join(".gitignore")) {
+                let base = match relative.is_empty() {
+                    true => String::new(),
+                    false => format!("{}/", relative),
+                };
+                rules.extend(
+                    contents
+                        .lines()
+                        .filter_map(|line| IgnoreRule::parse(line, &base)),
+                );
+            }
+        }
+        let mut entries =
+            match fs::read_dir(dir).and_then(|entries| entries.collect::<Result<Vec<_>, _>>()) {
+                Ok(entries) => entries,
+                Err(err) => return found.push(Err((dir.to_path_buf(), err))),
+            };
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !self.hidden && name.starts_with('.') {
+                continue;
+            }
+            let relative = match relative.is_empty() {
+                true => name,
+                false => format!("{}/{}", relative, name),
+            };
+            let is_dir = match self.file_type(&path) {
+                Ok(Some(is_dir)) => is_dir,
+                Ok(None) => continue,
+                Err(err) => {
+                    found.push(Err((path, err)));
+                    continue;
+                },
+            };
+            if self.exclude.iter().any(|glob| glob.matches(&relative))
+                || ignored(&rules, &relative, is_dir)
+            {
+                continue;
+            }
+            if !is_dir {
+                if self.include.is_empty()
+                    || self.include.iter().any(|glob| glob.matches(&relative))
+                {
+                    found.push(Ok(path));
+                }
+                continue;
+            }
+            // Linked directories could lead back to one being walked
+            if self.symlinks == Symlinks::Follow
+                && path
+                    .canonicalize()
+                    .is_ok_and(|canonical| !visited.insert(canonical))
+            {
+                continue;
+            }
+            self.walk_dir(&path, &relative, &rules, visited, found);
+        }
+    }
+
+    /// Whether `path` is a directory, or `None` if it should be left out
+    fn file_type(&self, path: &Path) -> Result<Option<bool>, io::Error> {
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(Some(metadata.is_dir()));
+        }
+        match self.symlinks {
+            Symlinks::Skip => Ok(None),
+            Symlinks::Files => Ok(fs::metadata(path)?.is_file().then_some(false)),
+            Symlinks::Follow => Ok(Some(fs::metadata(path)?.is_dir())),
+        }
+    }
+}
+
+/// Whether the last of `rules` matching `path` ignores it
+fn ignored(rules: &[IgnoreRule], path: &str, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}